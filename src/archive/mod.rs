@@ -0,0 +1,39 @@
+pub(crate) mod tar;
+pub(crate) mod zip;
+use failure::{bail, Error};
+
+/// A parsed archive whose members can be read and replaced.
+pub(crate) enum Archive {
+    Tar(tar::Tar),
+    Zip(zip::Zip),
+}
+
+impl Archive {
+    /// Parses `data` as one of the supported archive formats.
+    pub(crate) fn parse(data: Vec<u8>) -> Result<Archive, Error> {
+        if tar::is_tar(&data) {
+            Ok(Archive::Tar(tar::Tar::parse(data)?))
+        } else if zip::is_zip(&data) {
+            Ok(Archive::Zip(zip::Zip::parse(data)?))
+        } else {
+            bail!("The input is no supported archive (tar, zip)")
+        }
+    }
+
+    /// Returns the (decompressed) content of the member `path`.
+    pub(crate) fn member(&self, path: &str) -> Result<Vec<u8>, Error> {
+        match self {
+            Archive::Tar(tar) => Ok(tar.member(path)?.to_vec()),
+            Archive::Zip(zip) => zip.member(path),
+        }
+    }
+
+    /// Returns the archive with `data` as the new content of the member
+    /// `path`, the metadata of all members is kept.
+    pub(crate) fn replace(&self, path: &str, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Archive::Tar(tar) => tar.replace(path, data),
+            Archive::Zip(zip) => zip.replace(path, data),
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use failure::{bail, Error};
+use std::ops::Range;
+
+static BLOCK: usize = 512;
+static TYPEFLAG: usize = 156;
+// header fields, the ranges are constants as they are not `Copy`
+const SIZE: Range<usize> = 124..136;
+const CHECKSUM: Range<usize> = 148..156;
+const MAGIC: Range<usize> = 257..265;
+
+/// Key and value records of a pax extended header.
+type PaxRecords = Vec<(String, Vec<u8>)>;
+
+/// A member of the archive, including the headers which precede it (GNU long
+/// names, pax extended headers).
+#[derive(Debug)]
+struct Entry {
+    name: String,
+    /// Offset of the first header which belongs to the member.
+    start: usize,
+    /// Offset of the member's own header.
+    header: usize,
+    size: usize,
+    typeflag: u8,
+    /// Offset, end and records of a pax extended header.
+    pax: Option<(usize, usize, PaxRecords)>,
+}
+
+impl Entry {
+    fn end(&self) -> usize {
+        self.header + BLOCK + padded(self.size)
+    }
+}
+
+pub(crate) struct Tar {
+    data: Vec<u8>,
+    entries: Vec<Entry>,
+}
+
+fn padded(size: usize) -> usize {
+    (size + BLOCK - 1) & !(BLOCK - 1)
+}
+
+/// Parses a numeric field, which is either octal or base-256 encoded (GNU).
+fn parse_number(field: &[u8]) -> Result<usize, Error> {
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..]
+            .iter()
+            .fold((field[0] & 0x7f) as usize, |n, b| n << 8 | *b as usize));
+    }
+    let digits: Vec<u8> = field
+        .iter()
+        .skip_while(|b| **b == b' ')
+        .take_while(|b| **b != 0 && **b != b' ')
+        .cloned()
+        .collect();
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    Ok(usize::from_str_radix(std::str::from_utf8(&digits)?, 8)?)
+}
+
+fn checksum(header: &[u8]) -> usize {
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, b)| if CHECKSUM.contains(&i) { b' ' } else { *b } as usize)
+        .sum()
+}
+
+/// Returns whether `data` begins with a tar header.
+pub(crate) fn is_tar(data: &[u8]) -> bool {
+    if data.len() < BLOCK {
+        return false;
+    }
+    let header = &data[..BLOCK];
+    &header[MAGIC.start..MAGIC.start + 5] == b"ustar"
+        || parse_number(&header[CHECKSUM]).ok() == Some(checksum(header))
+}
+
+/// Writes `size` to the size field, base-256 encoded if it exceeds the 11
+/// octal digits, and updates the checksum.
+fn set_size(header: &mut [u8], size: usize) {
+    if size < 1 << 33 {
+        header[SIZE].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+    } else {
+        header[SIZE.start] = 0x80;
+        header[SIZE.start + 1..SIZE.end].copy_from_slice(&(size as u128).to_be_bytes()[5..]);
+    }
+    let sum = checksum(header);
+    header[CHECKSUM].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+}
+
+/// Returns the zero terminated string of `field`.
+fn c_str(field: &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+/// Parses the `<length> <key>=<value>\n` records of a pax extended header.
+fn parse_pax(mut data: &[u8]) -> Result<PaxRecords, Error> {
+    let mut records = vec![];
+    while !data.is_empty() && data[0] != 0 {
+        let space = match data.iter().position(|b| *b == b' ') {
+            Some(space) => space,
+            None => bail!("Invalid pax record"),
+        };
+        let len: usize = std::str::from_utf8(&data[..space])?.parse()?;
+        if len <= space + 1 || len > data.len() || data[len - 1] != b'\n' {
+            bail!("Invalid pax record")
+        }
+        let record = &data[space + 1..len - 1];
+        let eq = match record.iter().position(|b| *b == b'=') {
+            Some(eq) => eq,
+            None => bail!("Invalid pax record"),
+        };
+        records.push((
+            String::from_utf8(record[..eq].to_vec())?,
+            record[eq + 1..].to_vec(),
+        ));
+        data = &data[len..];
+    }
+    Ok(records)
+}
+
+fn write_pax(records: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut data = vec![];
+    for (key, value) in records {
+        // the length includes its own digits
+        let body = key.len() + value.len() + 3;
+        let mut len = body + 1;
+        while len != body + len.to_string().len() {
+            len = body + len.to_string().len();
+        }
+        data.extend_from_slice(format!("{} {}=", len, key).as_bytes());
+        data.extend_from_slice(value);
+        data.push(b'\n');
+    }
+    data
+}
+
+fn pad(data: &mut Vec<u8>) {
+    data.resize(padded(data.len()), 0);
+}
+
+impl Tar {
+    pub(crate) fn parse(data: Vec<u8>) -> Result<Tar, Error> {
+        let mut entries = vec![];
+        let mut offset = 0;
+        let mut start = None;
+        let mut long_name = None;
+        let mut pax = None;
+        while offset + BLOCK <= data.len() {
+            let header = &data[offset..offset + BLOCK];
+            if header.iter().all(|b| *b == 0) {
+                break;
+            }
+            if parse_number(&header[CHECKSUM])? != checksum(header) {
+                bail!("Invalid tar header checksum at offset {}", offset)
+            }
+            let size = parse_number(&header[SIZE])?;
+            let content = offset + BLOCK;
+            let end = content + padded(size);
+            if content + size > data.len() {
+                bail!("Truncated tar member at offset {}", offset)
+            }
+            let content = &data[content..content + size];
+            match header[TYPEFLAG] {
+                b'L' => {
+                    start = start.or(Some(offset));
+                    long_name = Some(c_str(content));
+                }
+                b'x' => {
+                    start = start.or(Some(offset));
+                    pax = Some((offset, end, parse_pax(content)?));
+                }
+                typeflag => {
+                    let path = pax.as_ref().and_then(|(_, _, records)| {
+                        records
+                            .iter()
+                            .find(|(key, _)| key == "path")
+                            .map(|(_, value)| String::from_utf8_lossy(value).to_string())
+                    });
+                    let name = match path.or_else(|| long_name.take()) {
+                        Some(name) => name,
+                        None => {
+                            let name = c_str(&header[..100]);
+                            let prefix = c_str(&header[345..500]);
+                            if &header[MAGIC] == b"ustar\x0000" && !prefix.is_empty() {
+                                format!("{}/{}", prefix, name)
+                            } else {
+                                name
+                            }
+                        }
+                    };
+                    entries.push(Entry {
+                        name,
+                        start: start.take().unwrap_or(offset),
+                        header: offset,
+                        size,
+                        typeflag,
+                        pax: pax.take(),
+                    });
+                    long_name = None;
+                }
+            }
+            offset = end;
+        }
+        Ok(Tar { data, entries })
+    }
+
+    fn find(&self, path: &str) -> Result<&Entry, Error> {
+        let path = path.trim_start_matches("./");
+        let entry = match self
+            .entries
+            .iter()
+            .rev()
+            .find(|e| e.name.trim_start_matches("./") == path)
+        {
+            Some(entry) => entry,
+            None => bail!("There is no member '{}' in the archive", path),
+        };
+        match entry.typeflag {
+            0 | b'0' | b'7' => Ok(entry),
+            _ => bail!("The member '{}' is no regular file", path),
+        }
+    }
+
+    pub(crate) fn member(&self, path: &str) -> Result<&[u8], Error> {
+        let entry = self.find(path)?;
+        let content = entry.header + BLOCK;
+        Ok(&self.data[content..content + entry.size])
+    }
+
+    pub(crate) fn replace(&self, path: &str, content: &[u8]) -> Result<Vec<u8>, Error> {
+        let entry = self.find(path)?;
+        let mut data = self.data[..entry.start].to_vec();
+        match &entry.pax {
+            Some((offset, end, records)) if records.iter().any(|(key, _)| key == "size") => {
+                let records: PaxRecords = records
+                    .iter()
+                    .map(|(key, value)| match key.as_str() {
+                        "size" => (key.clone(), content.len().to_string().into_bytes()),
+                        _ => (key.clone(), value.clone()),
+                    })
+                    .collect();
+                let pax = write_pax(&records);
+                data.extend_from_slice(&self.data[*offset..offset + BLOCK]);
+                let header = data.len() - BLOCK;
+                set_size(&mut data[header..], pax.len());
+                data.extend_from_slice(&pax);
+                pad(&mut data);
+                data.extend_from_slice(&self.data[*end..entry.header]);
+            }
+            _ => data.extend_from_slice(&self.data[entry.start..entry.header]),
+        }
+        data.extend_from_slice(&self.data[entry.header..entry.header + BLOCK]);
+        let header = data.len() - BLOCK;
+        set_size(&mut data[header..], content.len());
+        data.extend_from_slice(content);
+        pad(&mut data);
+        data.extend_from_slice(&self.data[entry.end()..]);
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Creates a ustar header for a member of `size` bytes.
+    fn header(name: &str, size: usize, typeflag: u8) -> Vec<u8> {
+        let mut header = vec![0; BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[136..148].copy_from_slice(b"13621543210\0");
+        header[TYPEFLAG] = typeflag;
+        header[MAGIC].copy_from_slice(b"ustar\x0000");
+        set_size(&mut header, size);
+        header
+    }
+
+    fn member(data: &mut Vec<u8>, name: &str, content: &[u8], typeflag: u8) {
+        data.extend_from_slice(&header(name, content.len(), typeflag));
+        data.extend_from_slice(content);
+        pad(data);
+    }
+
+    pub(crate) fn archive() -> Vec<u8> {
+        let long_name = format!("{}/config.bin", "d".repeat(120));
+        let pax = write_pax(&[
+            ("path".to_string(), b"etc/pax.txt".to_vec()),
+            ("size".to_string(), b"5".to_vec()),
+        ]);
+        let mut data = vec![];
+        member(&mut data, "etc/", b"", b'5');
+        member(&mut data, "etc/hosts", b"127.0.0.1 localhost\n", b'0');
+        member(&mut data, "././@LongLink", long_name.as_bytes(), b'L');
+        member(&mut data, "dddd/config.bin", b"Hallo", b'0');
+        member(&mut data, "PaxHeaders/pax.txt", &pax, b'x');
+        member(&mut data, "etc/pax.txt", b"12345", b'0');
+        member(&mut data, "last", &[0xff; 600], b'0');
+        data.extend_from_slice(&[0; 1024]);
+        data
+    }
+
+    #[test]
+    fn test_pax() {
+        let records = vec![
+            ("path".to_string(), b"a".to_vec()),
+            ("comment".to_string(), vec![b'x'; 92]),
+        ];
+        let data = write_pax(&records);
+        assert_eq!(&data[..9], b"9 path=a\n");
+        assert_eq!(&data[9..13], b"105 ");
+        assert_eq!(parse_pax(&data).unwrap(), records);
+        assert!(parse_pax(b"11 path=a\n").is_err());
+    }
+
+    #[test]
+    fn test_member() {
+        let data = archive();
+        assert!(is_tar(&data));
+        assert!(!is_tar(&[0; 512]));
+        let tar = Tar::parse(data).unwrap();
+        assert_eq!(tar.member("etc/hosts").unwrap(), b"127.0.0.1 localhost\n");
+        assert_eq!(tar.member("./etc/hosts").unwrap(), b"127.0.0.1 localhost\n");
+        let long_name = format!("{}/config.bin", "d".repeat(120));
+        assert_eq!(tar.member(&long_name).unwrap(), b"Hallo");
+        assert_eq!(tar.member("etc/pax.txt").unwrap(), b"12345");
+        assert!(tar.member("etc").is_err());
+        assert!(tar.member("etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_replace() {
+        let tar = Tar::parse(archive()).unwrap();
+        let long_name = format!("{}/config.bin", "d".repeat(120));
+        let mut data = tar.replace("etc/hosts", b"::1 localhost\n").unwrap();
+        for (name, content) in vec![
+            (long_name.as_str(), vec![b'x'; 513]),
+            ("etc/pax.txt", b"123456789".to_vec()),
+            ("last", vec![]),
+        ] {
+            data = Tar::parse(data).unwrap().replace(name, &content).unwrap();
+        }
+        let tar = Tar::parse(data).unwrap();
+        assert_eq!(tar.member("etc/hosts").unwrap(), b"::1 localhost\n");
+        assert_eq!(tar.member(&long_name).unwrap(), vec![b'x'; 513].as_slice());
+        assert_eq!(tar.member("etc/pax.txt").unwrap(), b"123456789");
+        assert_eq!(tar.member("last").unwrap(), b"");
+        assert_eq!(tar.entries.len(), 5);
+        let entry = tar.find("etc/pax.txt").unwrap();
+        let (_, _, records) = entry.pax.as_ref().unwrap();
+        assert_eq!(records[1], ("size".to_string(), b"9".to_vec()));
+        // mtime is kept
+        let header = &tar.data[entry.header..entry.header + BLOCK];
+        assert_eq!(&header[136..148], b"13621543210\0");
+    }
+
+    #[test]
+    fn test_numbers() {
+        let mut header = header("big", 0, b'0');
+        assert_eq!(parse_number(&header[SIZE]).unwrap(), 0);
+        set_size(&mut header, 1 << 34);
+        assert_eq!(parse_number(&header[SIZE]).unwrap(), 1 << 34);
+        assert_eq!(parse_number(&header[CHECKSUM]).unwrap(), checksum(&header));
+        assert_eq!(parse_number(b"  644 \0").unwrap(), 0o644);
+    }
+}
@@ -0,0 +1,439 @@
+use crate::checksum::crc32;
+use failure::{bail, Error};
+use std::io::{Read, Write};
+
+static MAGIC: &[u8] = b"PK\x03\x04";
+static END_MAGIC: &[u8] = b"PK\x05\x06";
+static CENTRAL_MAGIC: &[u8] = b"PK\x01\x02";
+static DESCRIPTOR_MAGIC: &[u8] = b"PK\x07\x08";
+static LOCAL_LEN: usize = 30;
+static CENTRAL_LEN: usize = 46;
+static END_LEN: usize = 22;
+static FLAG_ENCRYPTED: u16 = 1;
+static FLAG_DESCRIPTOR: u16 = 1 << 3;
+static STORED: u16 = 0;
+static DEFLATED: u16 = 8;
+/// Alignment of the content of STORED members, as done by zipalign.
+static ALIGNMENT: usize = 4;
+/// Extra field of zipalign which pads the local header to align the content.
+static ALIGNMENT_EXTRA: u16 = 0xd935;
+
+fn le16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn le32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn put16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// A member as described by its central directory record.
+#[derive(Debug)]
+struct Entry {
+    name: String,
+    /// Offset of the central directory record.
+    central: usize,
+    flags: u16,
+    method: u16,
+    compressed_size: usize,
+    local: usize,
+}
+
+pub(crate) struct Zip {
+    data: Vec<u8>,
+    entries: Vec<Entry>,
+    /// Offset of the end of central directory record.
+    end: usize,
+}
+
+/// Searches the end of central directory record, which is followed by a
+/// comment of at most 64 KiB.
+fn find_end(data: &[u8]) -> Option<usize> {
+    if data.len() < END_LEN {
+        return None;
+    }
+    let min = data.len().saturating_sub(END_LEN + 0xffff);
+    (min..=data.len() - END_LEN)
+        .rev()
+        .find(|i| data[*i..].starts_with(END_MAGIC))
+}
+
+/// Checks for a local header at the beginning or, e.g. for self-extracting
+/// archives, an end of central directory record.
+pub(crate) fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(MAGIC) || find_end(data).is_some()
+}
+
+impl Zip {
+    pub(crate) fn parse(data: Vec<u8>) -> Result<Zip, Error> {
+        let end = match find_end(&data) {
+            Some(end) => end,
+            None => bail!("No end of central directory record found"),
+        };
+        let count = le16(&data, end + 10) as usize;
+        let mut central = le32(&data, end + 16) as usize;
+        if le32(&data, end + 16) == u32::MAX || le16(&data, end + 10) == u16::MAX {
+            bail!("ZIP64 archives are not supported")
+        }
+        let mut entries = vec![];
+        for _ in 0..count {
+            if central + CENTRAL_LEN > end || !data[central..].starts_with(CENTRAL_MAGIC) {
+                bail!("Invalid central directory record at offset {}", central)
+            }
+            let name_len = le16(&data, central + 28) as usize;
+            let len = CENTRAL_LEN
+                + name_len
+                + le16(&data, central + 30) as usize
+                + le16(&data, central + 32) as usize;
+            let entry = Entry {
+                name: String::from_utf8_lossy(&data[central + CENTRAL_LEN..][..name_len])
+                    .to_string(),
+                central,
+                flags: le16(&data, central + 8),
+                method: le16(&data, central + 10),
+                compressed_size: le32(&data, central + 20) as usize,
+                local: le32(&data, central + 42) as usize,
+            };
+            if entry.local + LOCAL_LEN > central || !data[entry.local..].starts_with(MAGIC) {
+                bail!("Invalid local header of '{}'", entry.name)
+            }
+            entries.push(entry);
+            central += len;
+        }
+        Ok(Zip { data, entries, end })
+    }
+
+    fn find(&self, path: &str) -> Result<&Entry, Error> {
+        let entry = match self.entries.iter().find(|e| e.name == path) {
+            Some(entry) => entry,
+            None => bail!("There is no member '{}' in the archive", path),
+        };
+        if entry.flags & FLAG_ENCRYPTED != 0 {
+            bail!("The member '{}' is encrypted", path)
+        }
+        if entry.compressed_size == u32::MAX as usize {
+            bail!("ZIP64 members are not supported")
+        }
+        Ok(entry)
+    }
+
+    /// Offset of the (compressed) content of `entry`.
+    fn content(&self, entry: &Entry) -> usize {
+        entry.local
+            + LOCAL_LEN
+            + le16(&self.data, entry.local + 26) as usize
+            + le16(&self.data, entry.local + 28) as usize
+    }
+
+    /// Appends the local header of `entry` to `data`. STORED members which
+    /// were aligned get a padding extra field, so their content stays aligned
+    /// at its new offset.
+    fn write_local_header(&self, entry: &Entry, data: &mut Vec<u8>) {
+        let start = self.content(entry);
+        let extra_begin = entry.local + LOCAL_LEN + le16(&self.data, entry.local + 26) as usize;
+        if entry.method != STORED || start % ALIGNMENT != 0 {
+            data.extend_from_slice(&self.data[entry.local..start]);
+            return;
+        }
+
+        // the fields except for a previous padding, a remainder of zeros is
+        // the padding of older zipalign versions
+        let mut extra = vec![];
+        let mut rest = &self.data[extra_begin..start];
+        while rest.len() >= 4 && 4 + le16(rest, 2) as usize <= rest.len() {
+            let len = 4 + le16(rest, 2) as usize;
+            if le16(rest, 0) != ALIGNMENT_EXTRA {
+                extra.extend_from_slice(&rest[..len]);
+            }
+            rest = &rest[len..];
+        }
+        if rest.iter().any(|b| *b != 0) {
+            data.extend_from_slice(&self.data[entry.local..start]);
+            return;
+        }
+
+        let header = data.len();
+        data.extend_from_slice(&self.data[entry.local..extra_begin]);
+        data.extend_from_slice(&extra);
+        // id, size and alignment followed by the padding
+        let pad = (ALIGNMENT - (data.len() + 6) % ALIGNMENT) % ALIGNMENT;
+        data.extend_from_slice(&ALIGNMENT_EXTRA.to_le_bytes());
+        data.extend_from_slice(&(2 + pad as u16).to_le_bytes());
+        data.extend_from_slice(&(ALIGNMENT as u16).to_le_bytes());
+        data.resize(data.len() + pad, 0);
+        put16(data, header + 28, (extra.len() + 6 + pad) as u16);
+    }
+
+    pub(crate) fn member(&self, path: &str) -> Result<Vec<u8>, Error> {
+        let entry = self.find(path)?;
+        let content = self.content(entry);
+        if content + entry.compressed_size > self.data.len() {
+            bail!("The member '{}' is truncated", path)
+        }
+        let compressed = &self.data[content..content + entry.compressed_size];
+        let data = match entry.method {
+            m if m == STORED => compressed.to_vec(),
+            m if m == DEFLATED => {
+                let mut data = vec![];
+                flate2::read::DeflateDecoder::new(compressed).read_to_end(&mut data)?;
+                data
+            }
+            m => bail!("Unsupported compression method {} of '{}'", m, path),
+        };
+        if crc32(&data) != le32(&self.data, entry.central + 16) {
+            bail!("CRC-32 mismatch of member '{}'", path)
+        }
+        Ok(data)
+    }
+
+    /// Rewrites the archive with the new `content` of `path`, which is stored
+    /// with its previous compression method. The offsets of the following
+    /// members and of the central directory are adjusted, data between the
+    /// members (e.g. an APK Signing Block) is kept but not updated.
+    pub(crate) fn replace(&self, path: &str, content: &[u8]) -> Result<Vec<u8>, Error> {
+        let target = self.find(path)?;
+        let compressed = match target.method {
+            m if m == STORED => content.to_vec(),
+            m if m == DEFLATED => {
+                let mut e =
+                    flate2::write::DeflateEncoder::new(vec![], flate2::Compression::default());
+                e.write_all(content)?;
+                e.finish()?
+            }
+            m => bail!("Unsupported compression method {} of '{}'", m, path),
+        };
+        let crc = crc32(content);
+
+        // local headers and content in the order of the file
+        let mut locals: Vec<&Entry> = self.entries.iter().collect();
+        locals.sort_by_key(|e| e.local);
+        let central = le32(&self.data, self.end + 16) as usize;
+        let first = locals.first().map(|e| e.local).unwrap_or(central);
+        let mut data = self.data[..first].to_vec();
+        let mut offsets = std::collections::HashMap::new();
+        for (i, entry) in locals.iter().enumerate() {
+            let next = locals.get(i + 1).map(|e| e.local).unwrap_or(central);
+            let start = self.content(entry);
+            let header = data.len();
+            offsets.insert(entry.local, header);
+            self.write_local_header(entry, &mut data);
+            if entry.local == target.local {
+                // sizes are stored in the local header instead of a descriptor
+                let flags = le16(&data, header + 6) & !FLAG_DESCRIPTOR;
+                put16(&mut data, header + 6, flags);
+                put32(&mut data, header + 14, crc);
+                put32(&mut data, header + 18, compressed.len() as u32);
+                put32(&mut data, header + 22, content.len() as u32);
+                data.extend_from_slice(&compressed);
+
+                let mut end = start + entry.compressed_size;
+                if entry.flags & FLAG_DESCRIPTOR != 0 {
+                    let signature = self.data[end.min(next)..next].starts_with(DESCRIPTOR_MAGIC);
+                    end += if signature { 16 } else { 12 };
+                }
+                if end > next {
+                    bail!("The member '{}' is truncated", path)
+                }
+                data.extend_from_slice(&self.data[end..next]);
+            } else {
+                data.extend_from_slice(&self.data[start..next]);
+            }
+        }
+
+        let new_central = data.len();
+        for entry in &self.entries {
+            let record = data.len();
+            let len = CENTRAL_LEN
+                + le16(&self.data, entry.central + 28) as usize
+                + le16(&self.data, entry.central + 30) as usize
+                + le16(&self.data, entry.central + 32) as usize;
+            data.extend_from_slice(&self.data[entry.central..entry.central + len]);
+            put32(&mut data, record + 42, offsets[&entry.local] as u32);
+            if entry.local == target.local {
+                put16(&mut data, record + 8, entry.flags & !FLAG_DESCRIPTOR);
+                put32(&mut data, record + 16, crc);
+                put32(&mut data, record + 20, compressed.len() as u32);
+                put32(&mut data, record + 24, content.len() as u32);
+            }
+        }
+        if data.len() > u32::MAX as usize || content.len() > u32::MAX as usize {
+            bail!("The archive would require ZIP64")
+        }
+
+        let end = data.len();
+        data.extend_from_slice(&self.data[self.end..]);
+        put32(&mut data, end + 12, (end - new_central) as u32);
+        put32(&mut data, end + 16, new_central as u32);
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an archive of `(name, content, method, descriptor)` members.
+    fn archive(members: &[(&str, &[u8], u16, bool)]) -> Vec<u8> {
+        let mut data = b"SFX stub".to_vec();
+        let mut central = vec![];
+        for (name, content, method, descriptor) in members {
+            let compressed = if *method == DEFLATED {
+                let mut e = flate2::write::DeflateEncoder::new(vec![], flate2::Compression::best());
+                e.write_all(content).unwrap();
+                e.finish().unwrap()
+            } else {
+                content.to_vec()
+            };
+            let flags = if *descriptor { FLAG_DESCRIPTOR } else { 0 };
+            let crc = crc32(content);
+            let mut header = vec![0; LOCAL_LEN];
+            header[..4].copy_from_slice(MAGIC);
+            put16(&mut header, 4, 20);
+            put16(&mut header, 6, flags);
+            put16(&mut header, 8, *method);
+            put16(&mut header, 10, 0x6000);
+            put16(&mut header, 12, 0x5021);
+            if !descriptor {
+                put32(&mut header, 14, crc);
+                put32(&mut header, 18, compressed.len() as u32);
+                put32(&mut header, 22, content.len() as u32);
+            }
+            put16(&mut header, 26, name.len() as u16);
+
+            let mut record = vec![0; CENTRAL_LEN];
+            record[..4].copy_from_slice(CENTRAL_MAGIC);
+            record[4..30].copy_from_slice(&[&[20, 3][..], &header[4..]].concat()[..26]);
+            put32(&mut record, 16, crc);
+            put32(&mut record, 20, compressed.len() as u32);
+            put32(&mut record, 24, content.len() as u32);
+            put32(&mut record, 42, data.len() as u32);
+            central.extend_from_slice(&record);
+            central.extend_from_slice(name.as_bytes());
+
+            data.extend_from_slice(&header);
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(&compressed);
+            if *descriptor {
+                data.extend_from_slice(DESCRIPTOR_MAGIC);
+                data.extend_from_slice(&crc.to_le_bytes());
+                data.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+                data.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            }
+        }
+        let mut end = vec![0; END_LEN];
+        end[..4].copy_from_slice(END_MAGIC);
+        put16(&mut end, 8, members.len() as u16);
+        put16(&mut end, 10, members.len() as u16);
+        put32(&mut end, 12, central.len() as u32);
+        put32(&mut end, 16, data.len() as u32);
+        put16(&mut end, 20, 7);
+        data.extend_from_slice(&central);
+        data.extend_from_slice(&end);
+        data.extend_from_slice(b"comment");
+        data
+    }
+
+    #[test]
+    fn test_replace() {
+        let text = b"Hallo Welt, Hallo Welt, Hallo Welt!".to_vec();
+        let zip = Zip::parse(archive(&[
+            (
+                "META-INF/MANIFEST.MF",
+                b"Manifest-Version: 1.0\n",
+                DEFLATED,
+                false,
+            ),
+            ("assets/text.txt", &text, DEFLATED, true),
+            ("resources.arsc", b"\x02\x00\x0c\x00", STORED, false),
+        ]))
+        .unwrap();
+        assert_eq!(zip.member("assets/text.txt").unwrap(), text);
+        assert_eq!(zip.member("resources.arsc").unwrap(), b"\x02\x00\x0c\x00");
+        assert!(zip.member("assets").is_err());
+
+        let data = zip.replace("assets/text.txt", b"Servus").unwrap();
+        let data = Zip::parse(data)
+            .unwrap()
+            .replace("resources.arsc", b"\x02\x00\x0c\x00\x01")
+            .unwrap();
+        assert!(data.starts_with(b"SFX stub"));
+        assert!(data.ends_with(b"comment"));
+        let zip = Zip::parse(data).unwrap();
+        assert_eq!(zip.entries.len(), 3);
+        assert_eq!(
+            zip.member("META-INF/MANIFEST.MF").unwrap(),
+            b"Manifest-Version: 1.0\n"
+        );
+        assert_eq!(zip.member("assets/text.txt").unwrap(), b"Servus");
+        assert_eq!(
+            zip.member("resources.arsc").unwrap(),
+            b"\x02\x00\x0c\x00\x01"
+        );
+        let entry = zip.find("assets/text.txt").unwrap();
+        assert_eq!(entry.flags & FLAG_DESCRIPTOR, 0);
+        assert_eq!(entry.method, DEFLATED);
+        // modification time and date are kept
+        assert_eq!(le32(&zip.data, entry.local + 10), 0x5021_6000);
+        assert_eq!(le32(&zip.data, entry.central + 12), 0x5021_6000);
+    }
+
+    #[test]
+    fn test_signing_block() {
+        let mut data = archive(&[
+            ("a", b"abc", STORED, false),
+            ("classes.dex", b"dex\n035\0", DEFLATED, true),
+        ]);
+        // a block between the last member and the central directory
+        let end = find_end(&data).unwrap();
+        let central = le32(&data, end + 16) as usize;
+        put32(&mut data, end + 16, (central + 16) as u32);
+        data.splice(central..central, b"APK Sig Block 42".iter().cloned());
+
+        let data = Zip::parse(data)
+            .unwrap()
+            .replace("classes.dex", b"dex\n039\0")
+            .unwrap();
+        let zip = Zip::parse(data).unwrap();
+        assert_eq!(zip.member("classes.dex").unwrap(), b"dex\n039\0");
+        let central = le32(&zip.data, zip.end + 16) as usize;
+        assert_eq!(&zip.data[central - 16..central], b"APK Sig Block 42");
+    }
+
+    #[test]
+    fn test_alignment() {
+        // the content of "lib/a.so" is aligned, the one of "a" is not
+        let mut zip = Zip::parse(archive(&[
+            ("a", b"abc", STORED, false),
+            ("lib/a.so", b"\x7fELF", STORED, false),
+        ]))
+        .unwrap();
+        assert_eq!(zip.content(&zip.entries[1]) % ALIGNMENT, 0);
+        for content in vec![&b"abcd"[..], b"ab", b"abcdefg", b"abc"] {
+            zip = Zip::parse(zip.replace("a", content).unwrap()).unwrap();
+            assert_eq!(zip.member("a").unwrap(), content);
+            assert_eq!(zip.member("lib/a.so").unwrap(), b"\x7fELF");
+            assert_eq!(zip.content(&zip.entries[1]) % ALIGNMENT, 0);
+            // the previous padding is replaced
+            assert!(le16(&zip.data, zip.entries[1].local + 28) < 6 + ALIGNMENT as u16);
+            assert_eq!(le16(&zip.data, zip.entries[0].local + 28), 0);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(!is_zip(b"PK\x05"));
+        assert!(Zip::parse(b"PK\x03\x04".to_vec()).is_err());
+        let mut data = archive(&[("a", b"a", STORED, false)]);
+        data[LOCAL_LEN + 9] ^= 0xff;
+        assert!(Zip::parse(data).unwrap().member("a").is_err());
+    }
+}
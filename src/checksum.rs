@@ -0,0 +1,410 @@
+use failure::{bail, Error};
+use lazy_static::lazy_static;
+use std::str::FromStr;
+
+lazy_static! {
+    static ref CRC32_TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            let mut c = i as u32;
+            for _ in 0..8 {
+                c = if c & 1 != 0 {
+                    0xedb8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+            }
+            *entry = c;
+        }
+        table
+    };
+}
+
+/// Continues a CRC-32 (ISO-HDLC, as used by zip, png, ...) computation of
+/// `crc` with `data`, start with 0.
+pub(crate) fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut c = !crc;
+    for b in data {
+        c = CRC32_TABLE[((c ^ *b as u32) & 0xff) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// Parameters of a CRC in the Rocksoft model, as used by the CRC catalogue.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct Crc {
+    pub(crate) width: u32,
+    pub(crate) poly: u64,
+    pub(crate) init: u64,
+    pub(crate) refin: bool,
+    pub(crate) refout: bool,
+    pub(crate) xorout: u64,
+}
+
+impl Crc {
+    const fn new(width: u32, poly: u64, init: u64, refin: bool, refout: bool, xorout: u64) -> Crc {
+        Crc {
+            width,
+            poly,
+            init,
+            refin,
+            refout,
+            xorout,
+        }
+    }
+
+    fn mask(&self) -> u64 {
+        u64::MAX >> (64 - self.width)
+    }
+
+    /// Number of bytes of the CRC value.
+    pub(crate) fn len(&self) -> usize {
+        ((self.width + 7) >> 3) as usize
+    }
+
+    /// Looks up a preset by its name (or alias) of the catalogue, ignoring
+    /// case.
+    pub(crate) fn preset(name: &str) -> Option<Crc> {
+        PRESETS
+            .iter()
+            .find(|p| p.0.iter().any(|n| n.eq_ignore_ascii_case(name)))
+            .map(|p| p.1)
+    }
+
+    pub(crate) fn presets() -> Vec<&'static str> {
+        PRESETS.iter().map(|p| p.0[0]).collect()
+    }
+
+    pub(crate) fn hasher(self) -> CrcHasher {
+        CrcHasher::new(self)
+    }
+}
+
+/// Known CRCs of the catalogue with their names and the CRC of "123456789".
+static PRESETS: &[(&[&str], Crc, u64)] = &[
+    (
+        &["CRC-8", "CRC-8/SMBUS"],
+        Crc::new(8, 0x07, 0, false, false, 0),
+        0xf4,
+    ),
+    (
+        &["CRC-8/AUTOSAR"],
+        Crc::new(8, 0x2f, 0xff, false, false, 0xff),
+        0xdf,
+    ),
+    (
+        &["CRC-8/MAXIM-DOW", "CRC-8/MAXIM"],
+        Crc::new(8, 0x31, 0, true, true, 0),
+        0xa1,
+    ),
+    (
+        &["CRC-16/ARC", "CRC-16"],
+        Crc::new(16, 0x8005, 0, true, true, 0),
+        0xbb3d,
+    ),
+    (
+        &["CRC-16/MODBUS"],
+        Crc::new(16, 0x8005, 0xffff, true, true, 0),
+        0x4b37,
+    ),
+    (
+        &["CRC-16/USB"],
+        Crc::new(16, 0x8005, 0xffff, true, true, 0xffff),
+        0xb4c8,
+    ),
+    (
+        &["CRC-16/IBM-3740", "CRC-16/CCITT-FALSE", "CRC-16/AUTOSAR"],
+        Crc::new(16, 0x1021, 0xffff, false, false, 0),
+        0x29b1,
+    ),
+    (
+        &["CRC-16/XMODEM"],
+        Crc::new(16, 0x1021, 0, false, false, 0),
+        0x31c3,
+    ),
+    (
+        &["CRC-16/KERMIT"],
+        Crc::new(16, 0x1021, 0, true, true, 0),
+        0x2189,
+    ),
+    (
+        &["CRC-16/IBM-SDLC", "CRC-16/X-25"],
+        Crc::new(16, 0x1021, 0xffff, true, true, 0xffff),
+        0x906e,
+    ),
+    (
+        &["CRC-16/GENIBUS"],
+        Crc::new(16, 0x1021, 0xffff, false, false, 0xffff),
+        0xd64e,
+    ),
+    (
+        &["CRC-32/ISO-HDLC", "CRC-32"],
+        Crc::new(32, 0x04c1_1db7, 0xffff_ffff, true, true, 0xffff_ffff),
+        0xcbf4_3926,
+    ),
+    (
+        &["CRC-32/BZIP2"],
+        Crc::new(32, 0x04c1_1db7, 0xffff_ffff, false, false, 0xffff_ffff),
+        0xfc89_1918,
+    ),
+    (
+        &["CRC-32/MPEG-2"],
+        Crc::new(32, 0x04c1_1db7, 0xffff_ffff, false, false, 0),
+        0x0376_e6e7,
+    ),
+    (
+        &["CRC-32/CKSUM", "CRC-32/POSIX"],
+        Crc::new(32, 0x04c1_1db7, 0, false, false, 0xffff_ffff),
+        0x765e_7680,
+    ),
+    (
+        &["CRC-32/JAMCRC"],
+        Crc::new(32, 0x04c1_1db7, 0xffff_ffff, true, true, 0),
+        0x340b_c6d9,
+    ),
+    (
+        &["CRC-32/ISCSI", "CRC-32C"],
+        Crc::new(32, 0x1edc_6f41, 0xffff_ffff, true, true, 0xffff_ffff),
+        0xe306_9283,
+    ),
+    (
+        &["CRC-64/ECMA-182"],
+        Crc::new(64, 0x42f0_e1eb_a9ea_3693, 0, false, false, 0),
+        0x6c40_df5f_0b49_7347,
+    ),
+    (
+        &["CRC-64/XZ"],
+        Crc::new(64, 0x42f0_e1eb_a9ea_3693, u64::MAX, true, true, u64::MAX),
+        0x995d_c9bb_df19_39fa,
+    ),
+];
+
+fn parse_u64(value: &str) -> Result<u64, Error> {
+    if value.starts_with("0x") || value.starts_with("0X") {
+        Ok(u64::from_str_radix(&value[2..], 16)?)
+    } else {
+        Ok(value.parse::<u64>()?)
+    }
+}
+
+fn parse_bool(value: Option<&str>) -> Result<bool, Error> {
+    Ok(match value {
+        None | Some("true") | Some("1") => true,
+        Some("false") | Some("0") => false,
+        Some(x) => bail!("Invalid boolean in CRC definition: {}", x),
+    })
+}
+
+impl FromStr for Crc {
+    type Err = Error;
+
+    /// Parses a preset name or a definition like
+    /// `poly=0x04C11DB7,init=0xFFFFFFFF,refin,refout,xorout=0xFFFFFFFF`. The
+    /// width defaults to the number of digits of a hexadecimal `poly`.
+    fn from_str(spec: &str) -> Result<Crc, Error> {
+        if let Some(crc) = Crc::preset(spec) {
+            return Ok(crc);
+        }
+
+        let mut crc = Crc::new(0, 0, 0, false, false, 0);
+        let mut width = None;
+        let mut hex_width = None;
+        let mut poly = None;
+        for param in spec.split(',') {
+            let mut kv = param.splitn(2, '=');
+            let key = kv.next().unwrap().trim();
+            let value = kv.next().map(str::trim);
+            let number = || match value {
+                Some(v) => parse_u64(v),
+                None => bail!("Missing value of '{}' in CRC definition", key),
+            };
+            match key {
+                "width" => width = Some(number()? as u32),
+                "poly" => {
+                    poly = Some(number()?);
+                    let digits = value.unwrap();
+                    if digits.starts_with("0x") || digits.starts_with("0X") {
+                        hex_width = Some(4 * (digits.len() as u32 - 2));
+                    }
+                }
+                "init" => crc.init = number()?,
+                "xorout" => crc.xorout = number()?,
+                "refin" => crc.refin = parse_bool(value)?,
+                "refout" => crc.refout = parse_bool(value)?,
+                x => bail!("Unknown CRC preset or parameter: {}", x),
+            }
+        }
+
+        crc.poly = match poly {
+            Some(poly) => poly,
+            None => bail!("CRC definition requires 'poly'"),
+        };
+        crc.width = match width.or(hex_width) {
+            Some(width) if (1..=64).contains(&width) => width,
+            Some(width) => bail!("CRC width {} is not within 1..64", width),
+            None => bail!("CRC definition requires 'width' for a decimal 'poly'"),
+        };
+        let mask = crc.mask();
+        if crc.poly & !mask != 0 || crc.init & !mask != 0 || crc.xorout & !mask != 0 {
+            bail!("CRC parameters exceed the width of {} bits", crc.width)
+        }
+        Ok(crc)
+    }
+}
+
+/// Reverses the lowest `width` bits of `value`.
+fn reflect(value: u64, width: u32) -> u64 {
+    value.reverse_bits() >> (64 - width)
+}
+
+/// Running computation of a `Crc`, table driven for widths of at least 8
+/// bits.
+#[derive(Clone)]
+pub(crate) struct CrcHasher {
+    crc: Crc,
+    table: Vec<u64>,
+    state: u64,
+}
+
+impl CrcHasher {
+    fn new(crc: Crc) -> CrcHasher {
+        let top = 1 << (crc.width - 1);
+        let mut table = vec![];
+        if crc.width >= 8 {
+            for i in 0..256u64 {
+                let mut c = i << (crc.width - 8);
+                for _ in 0..8 {
+                    c = if c & top != 0 {
+                        (c << 1) ^ crc.poly
+                    } else {
+                        c << 1
+                    };
+                }
+                table.push(c & crc.mask());
+            }
+        }
+        CrcHasher {
+            crc,
+            table,
+            state: crc.init,
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        let crc = &self.crc;
+        let mask = crc.mask();
+        for b in data {
+            let b = if crc.refin { b.reverse_bits() } else { *b };
+            if crc.width >= 8 {
+                let i = ((self.state >> (crc.width - 8)) ^ b as u64) & 0xff;
+                self.state = ((self.state << 8) ^ self.table[i as usize]) & mask;
+            } else {
+                for bit in (0..8).rev() {
+                    let msb = (self.state >> (crc.width - 1)) & 1;
+                    self.state = (self.state << 1) & mask;
+                    if msb ^ ((b >> bit) & 1) as u64 != 0 {
+                        self.state ^= crc.poly;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Number of bytes of the CRC value.
+    pub(crate) fn len(&self) -> usize {
+        self.crc.len()
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        let value = if self.crc.refout {
+            reflect(self.state, self.crc.width)
+        } else {
+            self.state
+        };
+        value ^ self.crc.xorout
+    }
+}
+
+/// Continues an Adler-32 computation of `adler` with `data`, start with 1.
+pub(crate) fn adler32_update(adler: u32, data: &[u8]) -> u32 {
+    let mut a = adler & 0xffff;
+    let mut b = adler >> 16;
+    for x in data {
+        a = (a + *x as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+pub(crate) fn adler32(data: &[u8]) -> u32 {
+    adler32_update(1, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        let c = crc32_update(crc32(b"1234"), b"56789");
+        assert_eq!(c, 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_presets() {
+        for (names, crc, check) in PRESETS {
+            let mut h = crc.hasher();
+            h.update(b"1234");
+            h.update(b"56789");
+            assert_eq!(h.finish(), *check, "{}", names[0]);
+            assert_eq!(names[0].parse::<Crc>().unwrap(), *crc);
+        }
+        assert_eq!(Crc::preset("crc-16/modbus").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_definition() {
+        let crc = "poly=0x04C11DB7,init=0xFFFFFFFF,refin,refout,xorout=0xFFFFFFFF"
+            .parse::<Crc>()
+            .unwrap();
+        assert_eq!(crc, Crc::preset("CRC-32").unwrap());
+        let crc = "width=16,poly=32773,init=65535,refin=true,refout=1"
+            .parse::<Crc>()
+            .unwrap();
+        assert_eq!(crc, Crc::preset("CRC-16/MODBUS").unwrap());
+
+        // CRC-5/USB, narrower than a byte
+        let crc = "width=5,poly=0x05,init=0x1f,refin,refout,xorout=0x1f"
+            .parse::<Crc>()
+            .unwrap();
+        let mut h = crc.hasher();
+        h.update(b"123456789");
+        assert_eq!(h.finish(), 0x19);
+
+        for spec in vec![
+            "",
+            "CRC-99",
+            "poly=5",
+            "width=8,poly=0x107",
+            "width=65,poly=0x07",
+            "poly=0x07,refin=yes",
+            "poly=0x07,foo=1",
+            "poly=0x07,init",
+        ] {
+            assert!(spec.parse::<Crc>().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32_update(adler32(b"Wiki"), b"pedia"), 0x11e6_0398);
+    }
+}
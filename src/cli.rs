@@ -1,5 +1,14 @@
+use crate::checksum::Crc;
+use crate::command::{inflate, transform};
+use crate::compression::Compression;
 use crate::defs;
-use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use crate::encoding;
+use crate::format::Format;
+use crate::hash;
+use crate::patch;
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
+use lazy_static::lazy_static;
+use std::ffi::OsString;
 
 static POS_HELP: &str =
     "Specifies a position and range/count for the operation, see POSITION section";
@@ -12,9 +21,61 @@ static POS_HELP_SEC: &str = "POSITION:
 \t\t\t  Example: 'foobar', 0:=2 == 'foo' or 3:=5 == 'bar'
 \t<begin>+<count>\t  Begin plus <count> (exclusive), requires <count> > 0.
 \t\t\t  The length includes the begin position: 0+10 is 10 bytes, from 0..9 (same as 0:9)
+\tNumbers are decimal, optionally with unit (e.g. 4kib), or hexadecimal with 0x prefix.
 ";
 
+lazy_static! {
+    static ref CRC_HELP: String = format!(
+        "CRC:
+\tA CRC is specified by a name of the CRC catalogue or by its parameters, e.g.
+\tpoly=0x04C11DB7,init=0xFFFFFFFF,refin,refout,xorout=0xFFFFFFFF
+\tThe width defaults to the number of digits of a hexadecimal poly, use width=<N> otherwise.
+\tKnown names: {}
+",
+        Crc::presets().join(", ")
+    );
+    static ref POS_CRC_HELP: String = format!("{}\n{}", POS_HELP_SEC, *CRC_HELP);
+}
+
+static DUMP_HELP: &str =
+    "The provided value/STDIN is a xxd or 'hexdump -C' text dump, its offsets are relative to <begin>";
+
+/// Subcommand `name` which combines a range with a key.
+fn key_subcommand<'a, 'b>(name: &'a str, about: &'a str) -> App<'a, 'b> {
+    SubCommand::with_name(name)
+        .about(about)
+        .after_help(POS_HELP_SEC)
+        .group(
+            ArgGroup::with_name("keys")
+                .args(&["key", "key-file"])
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("position")
+                .help(POS_HELP)
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("key")
+                .help("Repeating key, e.g. hex:5a or str:secret")
+                .long("key")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("key-file")
+                .help("File containing the repeating key")
+                .long("key-file")
+                .value_name("FILE")
+                .takes_value(true),
+        )
+}
+
 pub(crate) fn get_matches<'a>() -> ArgMatches<'a> {
+    get_matches_from(&std::env::args_os().collect::<Vec<_>>())
+}
+
+pub(crate) fn get_matches_from<'a>(args: &[OsString]) -> ArgMatches<'a> {
     App::new(clap::crate_name!())
         .version(clap::crate_version!())
         .author(clap::crate_authors!("\n"))
@@ -50,6 +111,40 @@ pub(crate) fn get_matches<'a>() -> ArgMatches<'a> {
                 .short("i")
                 .long(defs::IN_PLACE_P),
         )
+        .arg(
+            Arg::with_name(defs::HEXDUMP_P)
+                .help("Treat the input as a xxd or 'hexdump -C' text dump and convert it back to binary first")
+                .short("x")
+                .long(defs::HEXDUMP_P),
+        )
+        .arg(
+            Arg::with_name(defs::COMPRESS_P)
+                .help("Compress the output, by default derived from the extension of OUTPUT or \
+                       the compression of an in-place edited input")
+                .long(defs::COMPRESS_P)
+                .value_name("COMPRESSION")
+                .possible_values(Compression::variants()),
+        )
+        .arg(
+            Arg::with_name(defs::NO_DECOMPRESS_P)
+                .help("Don't decompress a gzip, xz, zstd or bzip2 compressed input or other file")
+                .long(defs::NO_DECOMPRESS_P)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name(defs::MEMBER_P)
+                .help("Operate on the member <PATH> of a tar or zip archive input, the archive is \
+                       written with the output as new content of the member")
+                .long(defs::MEMBER_P)
+                .value_name("PATH")
+                .conflicts_with(defs::HEXDUMP_P),
+        )
+        .arg(
+            Arg::with_name(defs::EXTRACT_P)
+                .help("Write the output for the archive member as is instead of the archive")
+                .long(defs::EXTRACT_P)
+                .conflicts_with(defs::IN_PLACE_P),
+        )
         .arg(
             Arg::with_name("file")
                 .help("Specify an input file, if not present, STDIN input is required (disables STDIN for subcommands)")
@@ -80,6 +175,11 @@ This should be an integer, where -1 specifies then end of the file/stream"##,
                         .short("v")
                         .takes_value(true)
                         .required(false),
+                )
+                .arg(
+                    Arg::with_name("dump")
+                        .help(DUMP_HELP)
+                        .long("dump"),
                 ),
         )
         .subcommand(
@@ -101,6 +201,11 @@ This should be an integer, where -1 specifies then end of the file/stream"##,
                         .short("v")
                         .takes_value(true)
                         .required(false),
+                )
+                .arg(
+                    Arg::with_name("dump")
+                        .help(DUMP_HELP)
+                        .long("dump"),
                 ),
         )
         .subcommand(
@@ -120,6 +225,441 @@ This should be an integer, where -1 specifies then end of the file/stream"##,
                 .about("Cut/extract bytes from file/input")
                 .visible_alias("extract")
                 .after_help(POS_HELP_SEC)
+                .arg(
+                    Arg::with_name("position")
+                        .help(
+                            "Specifies a position and range/count for the operation, see \
+                             POSITION section. Several ranges are extracted into files, given \
+                             by <POSITION>=<FILE> or --out-template",
+                        )
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("template")
+                        .help(
+                            "File name template for ranges without '=<FILE>', with the \
+                             placeholders {index}, {begin}, {end} (exclusive) and {len}, \
+                             append ':x' for hex (e.g. part_{index}_{begin:x}.bin)",
+                        )
+                        .long("out-template")
+                        .short("o")
+                        .value_name("TEMPLATE")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .help("Print the extracted bytes in the given representation")
+                        .long("format")
+                        .short("f")
+                        .takes_value(true)
+                        .possible_values(Format::variants())
+                        .default_value("raw"),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .help("Identifier used by the source code formats (c, rust, python)")
+                        .long("name")
+                        .takes_value(true)
+                        .default_value("blob"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Show the differing regions between the input and another file")
+                .after_help(
+                    "Regions are reported as 'POSITION -> POSITION' of the input and the other file, \
+                     see POSITION section of 'delete'/'cut'. 'at OFFSET' marks the point of an \
+                     insertion or deletion.",
+                )
+                .arg(
+                    Arg::with_name("new")
+                        .help("File which the input is compared with")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the regions as JSON")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("delta")
+                .about("Generate a shell script of bytie calls which transforms the input into another file")
+                .after_help(
+                    "The script is applied in-place on the file given as its first argument, \
+                     set BYTIE to use another bytie executable.",
+                )
+                .arg(
+                    Arg::with_name("new")
+                        .help("File which should result from applying the script")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("patch")
+                .about("Apply or create binary patches (IPS, UPS, BPS, VCDIFF, bsdiff)")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("apply")
+                        .about("Apply a patch on the input")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("Patch file")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("format")
+                                .help("Format of the patch, detected by its magic if not provided")
+                                .long("format")
+                                .short("f")
+                                .takes_value(true)
+                                .possible_values(patch::Format::variants()),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("create")
+                        .about("Create a patch which transforms the input into <TARGET>")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("Target file")
+                                .value_name("TARGET")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("format")
+                                .help("Format of the patch")
+                                .long("format")
+                                .short("f")
+                                .takes_value(true)
+                                .required(true)
+                                .possible_values(patch::Format::variants()),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("merge")
+                .about("Merge the changes of two files which derive from the input (base)")
+                .after_help(
+                    "Changes of both files which overlap or touch each other are conflicts, unless \
+                     they are identical. Conflicts are reported with their positions in the base, \
+                     ours and theirs file and no output is written.",
+                )
+                .arg(
+                    Arg::with_name("ours")
+                        .help("First file derived from the base")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("theirs")
+                        .help("Second file derived from the base")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("report")
+                        .help("Write the conflicts as JSON to <FILE>")
+                        .long("report")
+                        .value_name("FILE")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("hash")
+                .about("Compute a checksum or digest over the input or a range of it")
+                .after_help(POS_CRC_HELP.as_str())
+                .arg(
+                    Arg::with_name("algo")
+                        .help("Checksum or digest algorithm")
+                        .long("algo")
+                        .short("a")
+                        .takes_value(true)
+                        .possible_values(hash::Algorithm::variants())
+                        .default_value("sha256"),
+                )
+                .arg(
+                    Arg::with_name("crc")
+                        .help("Compute a CRC instead, see CRC section")
+                        .long("crc")
+                        .value_name("CRC")
+                        .takes_value(true)
+                        .conflicts_with("algo"),
+                )
+                .arg(
+                    Arg::with_name("position")
+                        .help("Range which is hashed, the whole input if not provided")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fixup")
+                .about("Compute a checksum over a range and store it at an offset of the input")
+                .after_help(POS_CRC_HELP.as_str())
+                .arg(
+                    Arg::with_name("algo")
+                        .help(
+                            "Checksum algorithm (see 'hash --algo') with optional endianness \
+                             suffix 'le' or 'be' (default), e.g. crc32le",
+                        )
+                        .takes_value(true)
+                        .required_unless("crc")
+                        .conflicts_with("crc"),
+                )
+                .arg(
+                    Arg::with_name("crc")
+                        .help("Compute a CRC instead of <algo>, see CRC section")
+                        .long("crc")
+                        .value_name("CRC")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("endian")
+                        .help("Byte order of the stored checksum, overrides the suffix of <algo>")
+                        .long("endian")
+                        .takes_value(true)
+                        .possible_values(&["le", "be"]),
+                )
+                .arg(
+                    Arg::with_name("over")
+                        .help("Range which is checksummed, '<begin>:' covers up to the end")
+                        .long("over")
+                        .value_name("POSITION")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("store")
+                        .help("Offset where the checksum is stored")
+                        .long("store")
+                        .value_name("OFFSET")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("width")
+                        .help(
+                            "Number of bytes which are stored, the checksum is truncated to its \
+                             least significant bytes or padded with zeros",
+                        )
+                        .long("width")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("split")
+                .about("Split the input into numbered files by size, count or pattern")
+                .after_help(
+                    "Files are named <prefix><index>, with a zero padded index of at least 3 \
+                     digits. The names of the written files are printed.",
+                )
+                .group(
+                    ArgGroup::with_name("mode")
+                        .args(&["size", "chunks", "at"])
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("size")
+                        .help("Size of each part, e.g. 1MiB or 0x10000")
+                        .long("size")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("chunks")
+                        .help("Number of parts of equal size")
+                        .long("chunks")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("at")
+                        .help(
+                            "Begin a new part at each match of the byte regex <PATTERN>, \
+                             e.g. '/\\xff\\xd8/'",
+                        )
+                        .long("at")
+                        .value_name("PATTERN")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("prefix")
+                        .help("Prefix of the file names, may contain a directory")
+                        .long("prefix")
+                        .takes_value(true)
+                        .default_value("part_"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("join")
+                .about("Concatenate the input with other files or ranges of them")
+                .after_help(
+                    "Parts are given as <FILE> or <FILE>@<POSITION>, the input is the first part. \
+                     With --align, each part is padded with the --fill byte to a multiple of \
+                     <ALIGN>, so each part begins at an aligned offset.",
+                )
+                .arg(
+                    Arg::with_name("parts")
+                        .help("Files to append, optionally with a range, e.g. kernel.bin@0x200")
+                        .value_name("PART")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("separator")
+                        .help("Bytes inserted between the parts, e.g. hex:00 or str:,")
+                        .long("separator")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("align")
+                        .help("Pad each part to a multiple of <ALIGN> bytes")
+                        .long("align")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("fill")
+                        .help("Byte value used for padding")
+                        .long("fill")
+                        .takes_value(true)
+                        .default_value("0"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("overlay")
+                .about("Write (a range of) another file over the input at an offset")
+                .after_help(
+                    "The rest of the input stays unchanged. Writing beyond the end of the input \
+                     fails unless --grow is given, a gap is filled with zeros.",
+                )
+                .arg(
+                    Arg::with_name("overlay")
+                        .help("File written over the input")
+                        .value_name("FILE")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("at")
+                        .help("Offset in the input")
+                        .long("at")
+                        .value_name("OFFSET")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("range")
+                        .help("Use only this range of <FILE>")
+                        .long("range")
+                        .value_name("POSITION")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("grow")
+                        .help("Allow the input to grow")
+                        .long("grow")
+                        .overrides_with("no-grow"),
+                )
+                .arg(
+                    Arg::with_name("no-grow")
+                        .help("Fail if the overlay exceeds the input (default)")
+                        .long("no-grow")
+                        .overrides_with("grow"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("copy")
+                .about("Copy a range of the input to another offset")
+                .after_help(POS_HELP_SEC)
+                .arg(
+                    Arg::with_name("position")
+                        .help(POS_HELP)
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .help("Target offset, refers to the unmodified input")
+                        .long("to")
+                        .value_name("OFFSET")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("overwrite")
+                        .help("Overwrite the bytes at the target instead of inserting")
+                        .long("overwrite"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("move")
+                .about("Move a range of the input to another offset")
+                .after_help(POS_HELP_SEC)
+                .arg(
+                    Arg::with_name("position")
+                        .help(POS_HELP)
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .help("Target offset, refers to the unmodified input")
+                        .long("to")
+                        .value_name("OFFSET")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("swap")
+                .about("Exchange two non-overlapping ranges of the input")
+                .after_help(POS_HELP_SEC)
+                .arg(
+                    Arg::with_name("first")
+                        .help("First range, see POSITION")
+                        .value_name("POSITION")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("second")
+                        .help("Second range, may differ in length from the first")
+                        .value_name("POSITION")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("transform")
+                .about("Reverse, byte-swap or bit-swap the input or a range of it")
+                .after_help(
+                    "Transformations:\n\
+                     \treverse\tReverse the order of all bytes\n\
+                     \tswap16\tSwap the byte order of each 16/32/64 bit word, the range has to be\n\
+                     \tswap32\ta multiple of the word size\n\
+                     \tswap64\n\
+                     \tnibble\tSwap the nibbles of each byte\n\
+                     \tbitrev\tReverse the bit order within each byte",
+                )
+                .arg(
+                    Arg::with_name("transform")
+                        .help("Transformation which is applied")
+                        .possible_values(transform::Transform::variants())
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("position")
+                        .help("Range which is transformed, the whole input if not provided")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(key_subcommand("xor", "XOR a range of the input with a key"))
+        .subcommand(key_subcommand("and", "AND a range of the input with a mask"))
+        .subcommand(key_subcommand("or", "OR a range of the input with a mask"))
+        .subcommand(
+            SubCommand::with_name("not")
+                .about("Invert the bits of a range of the input")
+                .after_help(POS_HELP_SEC)
                 .arg(
                     Arg::with_name("position")
                         .help(POS_HELP)
@@ -127,5 +667,80 @@ This should be an integer, where -1 specifies then end of the file/stream"##,
                         .required(true),
                 ),
         )
-        .get_matches()
+        .subcommand(
+            SubCommand::with_name("encode")
+                .about("Replace the input or a range of it with its text encoding")
+                .after_help(POS_HELP_SEC)
+                .arg(
+                    Arg::with_name("encoding")
+                        .help("Encoding: hex, b64 (base64), b32 (base32) or a85 (ascii85)")
+                        .possible_values(encoding::Encoding::variants())
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("position")
+                        .help("Range which is encoded, the whole input if not provided")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("decode")
+                .about("Replace the encoded input or a range of it with the decoded data")
+                .after_help(POS_HELP_SEC)
+                .arg(
+                    Arg::with_name("encoding")
+                        .help("Encoding: hex, b64 (base64), b32 (base32) or a85 (ascii85)")
+                        .possible_values(encoding::Encoding::variants())
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("position")
+                        .help("Range which is decoded, the whole input if not provided")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("inflate")
+                .about("Replace a compressed range of the input with the decompressed data")
+                .after_help(POS_HELP_SEC)
+                .arg(
+                    Arg::with_name("position")
+                        .help("Begin of the compressed stream, its length is detected if no end is given")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .help("Format of the stream, detected by its header if not provided")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(inflate::Container::variants()),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("deflate")
+                .about("Replace a range of the input with its compressed data")
+                .after_help(POS_HELP_SEC)
+                .arg(
+                    Arg::with_name("position")
+                        .help("Range which is compressed")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .help("Format of the stream, zlib if not provided")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(inflate::Container::variants()),
+                )
+                .arg(
+                    Arg::with_name("level")
+                        .help("Compression level from 0 to 9")
+                        .long("level")
+                        .takes_value(true)
+                        .default_value("6"),
+                ),
+        )
+        .get_matches_from(args)
 }
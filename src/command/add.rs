@@ -1,18 +1,21 @@
+use crate::command::read_hexdump_value;
+use crate::hexdump;
+use crate::position::{parse_number, PositionParser};
 use clap::{value_t, ArgMatches};
 use failure::{bail, Error};
-use humanize_rs::bytes::Bytes;
 
 pub struct AddCommand {
     begin: usize,
     value: Option<Vec<u8>>,
+    dump: bool,
 }
 impl AddCommand {
-    pub fn from_matches(m: &ArgMatches) -> Result<Self, Error> {
-        let begin = value_t!(m, "begin", String)?;
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        let begin = parser.offset(&value_t!(m, "begin", String)?)?;
         let begin = if begin == "-1" {
             std::usize::MAX
         } else {
-            begin.parse::<Bytes>()?.size()
+            parse_number(&begin)?
         };
 
         let value = if let Ok(value) = value_t!(m, "value", String) {
@@ -20,8 +23,9 @@ impl AddCommand {
         } else {
             None
         };
+        let dump = m.is_present("dump");
 
-        Ok(Self { begin, value })
+        Ok(Self { begin, value, dump })
     }
 }
 
@@ -57,7 +61,10 @@ impl crate::command::Command for AddCommand {
             }
         }
 
-        if let Some(input) = input {
+        if self.dump {
+            let chunks = read_hexdump_value(blocksize, self.value.as_ref(), input)?;
+            out.write_all(&hexdump::to_bytes(&chunks))?;
+        } else if let Some(input) = input {
             let mut buffer = vec![0; blocksize];
             loop {
                 let n = input.read(&mut buffer)?;
@@ -99,6 +106,7 @@ mod tests {
         let cmd = AddCommand {
             begin: std::usize::MAX,
             value: Some(vec![3, 4, 5]),
+            dump: false,
         };
         let input = vec![0, 1, 2];
         let exp = vec![0, 1, 2, 3, 4, 5];
@@ -115,6 +123,7 @@ mod tests {
         let mut cmd = AddCommand {
             begin: 0,
             value: None,
+            dump: false,
         };
         let mut out: Vec<u8> = vec![];
 
@@ -150,6 +159,7 @@ mod tests {
         let mut cmd = AddCommand {
             begin: 0,
             value: None,
+            dump: false,
         };
         let mut out: Vec<u8> = vec![];
 
@@ -179,4 +189,35 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_dump() {
+        let cmd = AddCommand {
+            begin: 1,
+            value: Some(b"00000000  41 42 |AB|\n00000003  43 |C|\n".to_vec()),
+            dump: true,
+        };
+        let input = vec![0, 1, 2];
+        let exp = vec![0, 0x41, 0x42, 0, 0x43, 1, 2];
+
+        for bs in vec![1, 2, 3, 4, 10] {
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(bs, &mut input.as_slice(), &mut out, None).is_ok());
+            assert_eq!(exp, out);
+        }
+    }
+
+    #[test]
+    fn test_begin() {
+        let parser = PositionParser::new(|| bail!("no ELF"));
+        for (begin, exp) in vec![("0x10", 16), ("2kib", 2048), ("-1", std::usize::MAX)] {
+            let args: Vec<std::ffi::OsString> = vec!["bytie", "add", begin, "--value", "x"]
+                .into_iter()
+                .map(|a| a.into())
+                .collect();
+            let m = crate::cli::get_matches_from(&args);
+            let cmd = AddCommand::from_matches(m.subcommand_matches("add").unwrap(), &parser);
+            assert_eq!(cmd.unwrap().begin, exp);
+        }
+    }
 }
@@ -0,0 +1,99 @@
+use crate::command::{map_range, parse_bytes};
+use crate::position::{Position, PositionParser};
+use clap::{value_t, ArgMatches};
+use failure::{bail, Error};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Xor,
+    And,
+    Or,
+    Not,
+}
+
+/// Combines each byte of a range with a repeating key, the first key byte
+/// applies to the first byte of the range.
+pub struct BitwiseCommand {
+    op: Op,
+    key: Vec<u8>,
+    position: Position,
+}
+
+impl BitwiseCommand {
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser, op: Op) -> Result<Self, Error> {
+        let position = parser.position(&value_t!(m, "position", String)?)?;
+        let key = if op == Op::Not {
+            vec![0xff]
+        } else if let Ok(key) = value_t!(m, "key", String) {
+            parse_bytes(&key)?
+        } else {
+            std::fs::read(value_t!(m, "key-file", String)?)?
+        };
+        if key.is_empty() {
+            bail!("The key must not be empty")
+        }
+        Ok(Self { op, key, position })
+    }
+}
+
+impl crate::command::Command for BitwiseCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        let mut key = self.key.iter().cycle();
+        map_range(blocksize, source, out, &self.position, 1, &mut |data| {
+            for (b, k) in data.iter_mut().zip(&mut key) {
+                match self.op {
+                    Op::Xor | Op::Not => *b ^= k,
+                    Op::And => *b &= k,
+                    Op::Or => *b |= k,
+                }
+            }
+        })?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn test_bitwise() {
+        let input = b"__\x00\x0f\xf0\xff\x5a__";
+        for bs in vec![1, 2, 3, 4, 10] {
+            for (op, key, position, exp) in vec![
+                (Op::Xor, "hex:5a", "2+5", &b"__\x5a\x55\xaa\xa5\x00__"[..]),
+                (Op::Xor, "hex:ff00", "2+5", b"__\xff\x0f\x0f\xff\xa5__"),
+                (Op::Xor, "str:_", "0", b"\x00\x00_P\xaf\xa0\x05\x00\x00"),
+                (Op::And, "hex:3c", "2+5", b"__\x00\x0c\x30\x3c\x18__"),
+                (Op::Or, "hex:0102", "2+5", b"__\x01\x0f\xf1\xff\x5b__"),
+            ] {
+                let cmd = BitwiseCommand {
+                    op,
+                    key: parse_bytes(key).unwrap(),
+                    position: position.parse().unwrap(),
+                };
+                let mut out: Vec<u8> = vec![];
+                assert!(cmd.run(bs, &mut &input[..], &mut out, None).is_ok());
+                assert_eq!(out, exp.to_vec());
+            }
+
+            let cmd = BitwiseCommand {
+                op: Op::Not,
+                key: vec![0xff],
+                position: "2+5".parse().unwrap(),
+            };
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(bs, &mut &input[..], &mut out, None).is_ok());
+            assert_eq!(out, b"__\xff\xf0\x0f\x00\xa5__".to_vec());
+        }
+        assert!(parse_bytes("5a").is_err());
+    }
+}
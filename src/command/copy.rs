@@ -0,0 +1,141 @@
+use crate::command::read_all;
+use crate::position::{parse_number, Position, PositionParser};
+use clap::{value_t, ArgMatches};
+use failure::{bail, Error};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    /// Inserts a copy of the range at the target.
+    Insert,
+    /// Overwrites the bytes at the target with a copy of the range.
+    Overwrite,
+    /// Removes the range and inserts it at the target.
+    Move,
+}
+
+/// Copies or moves a range to another offset, which always refers to the
+/// unmodified input.
+pub struct CopyCommand {
+    position: Position,
+    to: usize,
+    mode: Mode,
+}
+
+impl CopyCommand {
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        let mode = if m.is_present("overwrite") {
+            Mode::Overwrite
+        } else {
+            Mode::Insert
+        };
+        Self::with_mode(m, parser, mode)
+    }
+
+    pub fn from_move_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        Self::with_mode(m, parser, Mode::Move)
+    }
+
+    fn with_mode(m: &ArgMatches, parser: &PositionParser, mode: Mode) -> Result<Self, Error> {
+        let position = parser.position(&value_t!(m, "position", String)?)?;
+        let to = parse_number(&parser.offset(&value_t!(m, "to", String)?)?)?;
+        Ok(Self { position, to, mode })
+    }
+
+    fn apply(&self, data: &mut Vec<u8>) -> Result<(), Error> {
+        let range = self.position.range(data.len())?;
+        let len = range.end - range.start;
+        if self.to > data.len() {
+            bail!("--to {} exceeds the input of {} bytes", self.to, data.len())
+        }
+        match self.mode {
+            Mode::Insert => {
+                let chunk = data[range].to_vec();
+                data.splice(self.to..self.to, chunk);
+            }
+            Mode::Overwrite => {
+                if self.to + len > data.len() {
+                    bail!("The copy at --to {} exceeds the end of the input", self.to)
+                }
+                data.copy_within(range, self.to);
+            }
+            Mode::Move => {
+                if range.start < self.to && self.to < range.end {
+                    bail!("--to {} lies within <POSITION>", self.to)
+                }
+                let chunk: Vec<u8> = data.drain(range.clone()).collect();
+                let to = if self.to >= range.end {
+                    self.to - len
+                } else {
+                    self.to
+                };
+                data.splice(to..to, chunk);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl crate::command::Command for CopyCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        let mut data = read_all(blocksize, source)?;
+        self.apply(&mut data)?;
+        out.write_all(&data)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn test_copy() {
+        let input = "HalloWelt";
+        for bs in vec![1, 2, 3, 4, 10] {
+            for (position, to, mode, exp) in vec![
+                ("0+5", 9, Mode::Insert, "HalloWeltHallo"),
+                ("5", 0, Mode::Insert, "WeltHalloWelt"),
+                ("1+2", 2, Mode::Insert, "HaallloWelt"),
+                ("0+5", 4, Mode::Overwrite, "HallHallo"),
+                ("5+3", 0, Mode::Overwrite, "WelloWelt"),
+                ("0+5", 9, Mode::Move, "WeltHallo"),
+                ("5", 0, Mode::Move, "WeltHallo"),
+                ("1+1", 4, Mode::Move, "HllaoWelt"),
+                ("1+1", 1, Mode::Move, "HalloWelt"),
+                ("1+1", 2, Mode::Move, "HalloWelt"),
+            ] {
+                let cmd = CopyCommand {
+                    position: position.parse().unwrap(),
+                    to,
+                    mode,
+                };
+                let mut out: Vec<u8> = vec![];
+                assert!(cmd.run(bs, &mut input.as_bytes(), &mut out, None).is_ok());
+                assert_eq!(exp, std::str::from_utf8(&out).unwrap());
+            }
+        }
+
+        for (position, to, mode) in vec![
+            ("0+5", 10, Mode::Insert),
+            ("5+5", 0, Mode::Insert),
+            ("0+5", 5, Mode::Overwrite),
+            ("0+5", 2, Mode::Move),
+        ] {
+            let cmd = CopyCommand {
+                position: position.parse().unwrap(),
+                to,
+                mode,
+            };
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(4, &mut input.as_bytes(), &mut out, None).is_err());
+        }
+    }
+}
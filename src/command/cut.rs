@@ -1,26 +1,193 @@
-use crate::position::Position;
-use clap::{value_t, ArgMatches};
+use crate::format::Format;
+use crate::position::{Position, PositionParser};
+use clap::{value_t, values_t, ArgMatches};
 use failure::{bail, Error};
+use lazy_static::lazy_static;
 use log::debug;
+use regex::{Captures, Regex};
 
 pub struct CutCommand {
     position: String,
+    /// Further `POSITION[=FILE]` arguments, extracted in the same pass.
+    more: Vec<String>,
+    /// File name template for positions without `=FILE`.
+    template: Option<String>,
+    format: Format,
+    name: String,
 }
 
-impl CutCommand {
-    pub fn from_matches(m: &ArgMatches) -> Result<Self, Error> {
-        let position = value_t!(m, "position", String)?;
-        Ok(Self { position })
+/// Splits `POSITION=FILE` into its parts, `=` of `:=` belongs to the
+/// position.
+fn split_target(arg: &str) -> (&str, Option<&str>) {
+    let b = arg.as_bytes();
+    match (0..b.len()).find(|i| b[*i] == b'=' && (*i == 0 || b[*i - 1] != b':')) {
+        Some(i) => (&arg[..i], Some(&arg[i + 1..])),
+        None => (arg, None),
     }
 }
 
-impl crate::command::Command for CutCommand {
-    fn run(
+/// Resolves the ELF references of the position of `POSITION=FILE`.
+fn resolve_target(arg: &str, parser: &PositionParser) -> Result<String, Error> {
+    let (position, file) = split_target(arg);
+    let position = parser.resolve(position)?;
+    Ok(match file {
+        Some(file) => format!("{}={}", position, file),
+        None => position,
+    })
+}
+
+/// Replaces `{index}`, `{begin}`, `{end}` (exclusive) and `{len}` of
+/// `template`, a `:x` suffix (e.g. `{begin:x}`) prints hexadecimal.
+fn render_template(
+    template: &str,
+    index: usize,
+    begin: usize,
+    end: Option<usize>,
+) -> Result<String, Error> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"\{(index|begin|end|len)(:x)?\}").unwrap();
+    }
+    if end.is_none()
+        && RE
+            .captures_iter(template)
+            .any(|c| &c[1] == "end" || &c[1] == "len")
+    {
+        bail!("{{end}} and {{len}} require a position with an end")
+    }
+    Ok(RE
+        .replace_all(template, |c: &Captures| {
+            let value = match &c[1] {
+                "index" => index,
+                "begin" => begin,
+                "end" => end.unwrap(),
+                _ => end.unwrap() - begin,
+            };
+            if c.get(2).is_some() {
+                format!("{:x}", value)
+            } else {
+                value.to_string()
+            }
+        })
+        .into_owned())
+}
+
+/// Range which is extracted into `file`.
+struct Target {
+    begin: usize,
+    end: Option<usize>,
+    file: String,
+    data: Vec<u8>,
+    writer: std::fs::File,
+}
+
+impl CutCommand {
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        let mut positions = values_t!(m, "position", String)?
+            .iter()
+            .map(|arg| resolve_target(arg, parser))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let position = positions.remove(0);
+        let template = value_t!(m, "template", String).ok();
+        let format = value_t!(m, "format", String)
+            .unwrap_or_else(|_| "raw".to_string())
+            .parse::<Format>()?;
+        let name = value_t!(m, "name", String).unwrap_or_else(|_| "blob".to_string());
+        Ok(Self {
+            position,
+            more: positions,
+            template,
+            format,
+            name,
+        })
+    }
+
+    /// Whether ranges are extracted into files instead of the output.
+    fn has_targets(&self) -> bool {
+        !self.more.is_empty() || self.template.is_some() || split_target(&self.position).1.is_some()
+    }
+
+    /// Parses all `POSITION[=FILE]` arguments, the files are only created
+    /// once every argument is valid.
+    fn targets(&self) -> Result<Vec<Target>, Error> {
+        let mut ranges = vec![];
+        for (index, arg) in std::iter::once(&self.position)
+            .chain(&self.more)
+            .enumerate()
+        {
+            let (position, file) = split_target(arg);
+            let position = position.parse::<Position>()?;
+            let end = position.end.map(|end| end + 1);
+            let file = match (file, &self.template) {
+                (Some(file), _) => file.to_string(),
+                (None, Some(template)) => render_template(template, index, position.begin, end)?,
+                (None, None) => bail!("{} requires '=<FILE>' or an --out-template", arg),
+            };
+            ranges.push((position.begin, end, file));
+        }
+        ranges
+            .into_iter()
+            .map(|(begin, end, file)| {
+                Ok(Target {
+                    begin,
+                    end,
+                    writer: std::fs::File::create(&file)?,
+                    file,
+                    data: vec![],
+                })
+            })
+            .collect()
+    }
+
+    /// Extracts several ranges into files in a single pass over `source`.
+    fn cut_targets(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+    ) -> Result<(), Error> {
+        let mut targets = self.targets()?;
+        let last = targets
+            .iter()
+            .map(|t| t.end.unwrap_or(usize::MAX))
+            .max()
+            .unwrap();
+        let mut buffer = vec![0; blocksize];
+        let mut offset = 0;
+        while offset < last {
+            let n = source.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            for t in targets.iter_mut() {
+                let begin = std::cmp::max(t.begin, offset);
+                let end = std::cmp::min(t.end.unwrap_or(offset + n), offset + n);
+                if begin < end {
+                    let data = &buffer[begin - offset..end - offset];
+                    if self.format == Format::Raw {
+                        std::io::Write::write_all(&mut t.writer, data)?;
+                    } else {
+                        t.data.extend_from_slice(data);
+                    }
+                }
+            }
+            offset += n;
+        }
+
+        for t in targets.iter_mut() {
+            if self.format != Format::Raw {
+                self.format.write(&t.data, &self.name, &mut t.writer)?;
+            }
+            writeln!(out, "{}", t.file)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    fn cut(
         &self,
         blocksize: usize,
         source: &mut dyn std::io::Read,
         out: &mut dyn std::io::Write,
-        _input: Option<&mut dyn std::io::Read>,
     ) -> Result<(), Error> {
         let position = self.position.parse::<Position>()?;
         let mut buffer = vec![0; blocksize];
@@ -93,6 +260,33 @@ impl crate::command::Command for CutCommand {
     }
 }
 
+impl crate::command::Command for CutCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        if self.has_targets() {
+            return self.cut_targets(blocksize, source, out);
+        }
+        if self.format == Format::Raw {
+            return self.cut(blocksize, source, out);
+        }
+
+        let mut data = vec![];
+        self.cut(blocksize, source, &mut data)?;
+        self.format.write(&data, &self.name, out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn edits_input(&self) -> bool {
+        !self.has_targets()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -102,6 +296,10 @@ mod tests {
     fn test_small_blocksize() {
         let mut cmd = CutCommand {
             position: "".to_string(),
+            more: vec![],
+            template: None,
+            format: Format::Raw,
+            name: "blob".to_string(),
         };
         let input = "HalloWelt";
         let mut out: Vec<u8> = vec![];
@@ -124,6 +322,10 @@ mod tests {
     fn test_big_blocksize() {
         let mut cmd = CutCommand {
             position: "".to_string(),
+            more: vec![],
+            template: None,
+            format: Format::Raw,
+            name: "blob".to_string(),
         };
         let input = r##"Lorem ipsum dolor sit amet, consectetur adipiscing elit,
             sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.
@@ -147,4 +349,79 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_format() {
+        let cmd = CutCommand {
+            position: "2+3".to_string(),
+            more: vec![],
+            template: None,
+            format: Format::Hex,
+            name: "blob".to_string(),
+        };
+        let input = "HalloWelt";
+
+        for bs in vec![1, 2, 3, 4, 10] {
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(bs, &mut input.as_bytes(), &mut out, None).is_ok());
+            assert_eq!("6c6c6f\n", std::str::from_utf8(&out).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_split_target() {
+        assert_eq!(split_target("0:10=a.bin"), ("0:10", Some("a.bin")));
+        assert_eq!(split_target("0:=10=a=b"), ("0:=10", Some("a=b")));
+        assert_eq!(split_target("0:=10"), ("0:=10", None));
+    }
+
+    #[test]
+    fn test_template() {
+        let t = render_template("p_{index}_{begin:x}_{end}_{len:x}.bin", 2, 16, Some(48));
+        assert_eq!(t.unwrap(), "p_2_10_48_20.bin");
+        assert_eq!(
+            render_template("{begin}{foo}", 0, 7, None).unwrap(),
+            "7{foo}"
+        );
+        assert!(render_template("{len}", 0, 7, None).is_err());
+    }
+
+    #[test]
+    fn test_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let mut cmd = CutCommand {
+            position: format!("0:5={}", path("a")),
+            more: vec!["3+4".to_string(), format!("8={}", path("c"))],
+            template: Some(path("part_{index}_{begin:x}")),
+            format: Format::Raw,
+            name: "blob".to_string(),
+        };
+        let input = "HalloWelt";
+
+        for bs in vec![1, 2, 3, 4, 10] {
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(bs, &mut input.as_bytes(), &mut out, None).is_ok());
+            let files = vec![path("a"), path("part_1_3"), path("c")];
+            assert_eq!(std::str::from_utf8(&out).unwrap(), files.join("\n") + "\n");
+            for (file, exp) in files.iter().zip(&["Hallo", "loWe", "t"]) {
+                assert_eq!(std::fs::read_to_string(file).unwrap(), *exp);
+            }
+        }
+
+        cmd.format = Format::Hex;
+        cmd.more.clear();
+        let mut out: Vec<u8> = vec![];
+        assert!(cmd.run(3, &mut input.as_bytes(), &mut out, None).is_ok());
+        assert_eq!(std::fs::read_to_string(path("a")).unwrap(), "48616c6c6f\n");
+
+        // invalid arguments leave the files of the valid ones untouched
+        cmd.template = None;
+        for more in vec!["3+4", "x=b"] {
+            cmd.more = vec![more.to_string()];
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(3, &mut input.as_bytes(), &mut out, None).is_err());
+            assert_eq!(std::fs::read_to_string(path("a")).unwrap(), "48616c6c6f\n");
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use crate::position::Position;
+use crate::position::{Position, PositionParser};
 use clap::{value_t, ArgMatches};
 use failure::{bail, Error};
 use log::debug;
@@ -7,8 +7,8 @@ pub struct DeleteCommand {
     position: String,
 }
 impl DeleteCommand {
-    pub fn from_matches(m: &ArgMatches) -> Result<Self, Error> {
-        let position = value_t!(m, "position", String)?;
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        let position = parser.resolve(&value_t!(m, "position", String)?)?;
         Ok(Self { position })
     }
 }
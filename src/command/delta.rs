@@ -0,0 +1,206 @@
+use crate::command::{open, read_all};
+use crate::defs;
+use crate::diff::{diff, Hunk};
+use crate::position::Position;
+use clap::{value_t, ArgMatches};
+use failure::Error;
+
+/// Maximum number of bytes passed to a single `printf` call in the script.
+static PRINTF_CHUNK: usize = 4096;
+
+/// A single `bytie` call of the edit script, offsets are relative to the
+/// data after all previous operations have been applied.
+#[derive(Debug, PartialEq, Eq)]
+enum Op {
+    Replace(usize, Vec<u8>),
+    Add(usize, Vec<u8>),
+    Delete(Position),
+}
+
+pub struct DeltaCommand {
+    new_file: String,
+    decompress: bool,
+}
+
+impl DeltaCommand {
+    pub fn from_matches(m: &ArgMatches) -> Result<Self, Error> {
+        let new_file = value_t!(m, "new", String)?;
+        let decompress = !m.is_present(defs::NO_DECOMPRESS_P);
+        Ok(Self {
+            new_file,
+            decompress,
+        })
+    }
+}
+
+/// Translates the hunks into `replace`, `add` and `delete` operations.
+fn ops(new: &[u8], hunks: &[Hunk]) -> Vec<Op> {
+    let mut ops = vec![];
+    let mut shift: isize = 0;
+    for h in hunks {
+        let at = (h.old.start as isize + shift) as usize;
+        let common = std::cmp::min(h.old.len(), h.new.len());
+        if common > 0 {
+            ops.push(Op::Replace(
+                at,
+                new[h.new.start..h.new.start + common].to_vec(),
+            ));
+        }
+        if h.new.len() > common {
+            ops.push(Op::Add(
+                at + common,
+                new[h.new.start + common..h.new.end].to_vec(),
+            ));
+        }
+        if h.old.len() > common {
+            ops.push(Op::Delete(Position::with_count(
+                at + common,
+                h.old.len() - common,
+            )));
+        }
+        shift += h.new.len() as isize - h.old.len() as isize;
+    }
+    ops
+}
+
+/// Quotes `data` for `printf`, non-printable bytes become octal escapes.
+fn printf_quote(data: &[u8]) -> String {
+    let mut s = String::new();
+    for b in data {
+        match b {
+            b'\\' | b'\'' | b'%' => s.push_str(&format!("\\{:03o}", b)),
+            0x20..=0x7e => s.push(*b as char),
+            _ => s.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    s
+}
+
+fn write_data(data: &[u8], out: &mut dyn std::io::Write) -> Result<(), Error> {
+    let calls: Vec<String> = data
+        .chunks(PRINTF_CHUNK)
+        .map(|c| format!("printf '{}'", printf_quote(c)))
+        .collect();
+    if calls.len() == 1 {
+        write!(out, "{} | ", calls[0])?;
+    } else {
+        write!(out, "{{ {}; }} | ", calls.join("; "))?;
+    }
+    Ok(())
+}
+
+fn write_script(ops: &[Op], out: &mut dyn std::io::Write) -> Result<(), Error> {
+    writeln!(out, "#!/bin/sh")?;
+    writeln!(out, "# bytie edit script, usage: sh <script> <file>")?;
+    writeln!(out, "set -e")?;
+    writeln!(out, "f=\"${{1:?missing <file>}}\"")?;
+    writeln!(out, "b=\"${{BYTIE:-bytie}}\"")?;
+    for op in ops {
+        match op {
+            Op::Replace(at, data) => {
+                write_data(data, out)?;
+                writeln!(out, "\"$b\" -i \"$f\" replace {}", at)?;
+            }
+            Op::Add(at, data) => {
+                write_data(data, out)?;
+                writeln!(out, "\"$b\" -i \"$f\" add {}", at)?;
+            }
+            Op::Delete(position) => {
+                writeln!(out, "\"$b\" -i \"$f\" delete {}", position)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl crate::command::Command for DeltaCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        let old = read_all(blocksize, source)?;
+        let new = read_all(blocksize, &mut open(&self.new_file, self.decompress)?)?;
+        let hunks = diff(&old, &new);
+
+        write_script(&ops(&new, &hunks), out)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{thread_rng, Rng};
+
+    fn replay(old: &[u8], ops: &[Op]) -> Vec<u8> {
+        let mut data = old.to_vec();
+        for op in ops {
+            match op {
+                Op::Replace(at, value) => {
+                    let end = std::cmp::min(at + value.len(), data.len());
+                    data.splice(*at..end, value.iter().cloned());
+                }
+                Op::Add(at, value) => {
+                    data.splice(*at..*at, value.iter().cloned());
+                }
+                Op::Delete(position) => {
+                    data.drain(position.begin..=position.end.unwrap());
+                }
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_ops() {
+        let old = b"0123456789abcdef";
+        let new = b"01X23456789cdefYZ";
+        let ops = ops(new, &diff(old, new));
+        assert_eq!(
+            ops,
+            vec![
+                Op::Add(2, b"X".to_vec()),
+                Op::Delete("11+2".parse::<Position>().unwrap()),
+                Op::Add(15, b"YZ".to_vec()),
+            ]
+        );
+        assert_eq!(replay(old, &ops), new.to_vec());
+    }
+
+    #[test]
+    fn test_random() {
+        let mut rng = thread_rng();
+        for _ in 0..50 {
+            let len: usize = rng.gen_range(0, 500);
+            let old: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            let mut new = old.clone();
+            for _ in 0..rng.gen_range(0, 10) {
+                let at = rng.gen_range(0, new.len() + 1);
+                let len = rng.gen_range(0, 10);
+                let value: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+                let end = std::cmp::min(at + rng.gen_range(0, 10), new.len());
+                new.splice(at..end, value);
+            }
+            let ops = ops(&new, &diff(&old, &new));
+            assert_eq!(replay(&old, &ops), new);
+        }
+    }
+
+    #[test]
+    fn test_script() {
+        let ops = vec![
+            Op::Replace(16, vec![0x00, b'A', b'\'', b'%']),
+            Op::Delete("20+2".parse::<Position>().unwrap()),
+        ];
+        let mut out: Vec<u8> = vec![];
+        assert!(write_script(&ops, &mut out).is_ok());
+        let script = std::str::from_utf8(&out).unwrap();
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("printf '\\000A\\047\\045' | \"$b\" -i \"$f\" replace 16\n"));
+        assert!(script.ends_with("\"$b\" -i \"$f\" delete 20+2\n"));
+    }
+}
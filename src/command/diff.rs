@@ -0,0 +1,190 @@
+use crate::command::{open, read_all};
+use crate::defs;
+use crate::diff::{diff, Hunk};
+use crate::encoding::hex_encode;
+use crate::position::describe;
+use clap::{value_t, ArgMatches};
+use failure::Error;
+use std::ops::Range;
+
+/// Number of bytes which are printed per hunk in the human readable output.
+static PREVIEW_LEN: usize = 32;
+
+pub struct DiffCommand {
+    new_file: String,
+    json: bool,
+    decompress: bool,
+}
+
+impl DiffCommand {
+    pub fn from_matches(m: &ArgMatches) -> Result<Self, Error> {
+        let new_file = value_t!(m, "new", String)?;
+        let json = m.is_present("json");
+        let decompress = !m.is_present(defs::NO_DECOMPRESS_P);
+        Ok(Self {
+            new_file,
+            json,
+            decompress,
+        })
+    }
+}
+
+fn preview(data: &[u8]) -> String {
+    if data.len() > PREVIEW_LEN {
+        format!(
+            "{}... ({} bytes)",
+            hex_encode(&data[0..PREVIEW_LEN]),
+            data.len()
+        )
+    } else {
+        hex_encode(data)
+    }
+}
+
+/// Formats the `range` of `data` as JSON object, the position is omitted for
+/// an empty range, which only has an offset.
+pub(crate) fn json_range(range: &Range<usize>, data: &[u8]) -> String {
+    let position = if range.is_empty() {
+        String::new()
+    } else {
+        format!("\"position\": \"{}\", ", describe(range))
+    };
+    format!(
+        "{{{}\"offset\": {}, \"length\": {}, \"data\": \"{}\"}}",
+        position,
+        range.start,
+        range.len(),
+        hex_encode(&data[range.clone()])
+    )
+}
+
+fn write_text(
+    old: &[u8],
+    new: &[u8],
+    hunks: &[Hunk],
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    for h in hunks {
+        writeln!(
+            out,
+            "{} {} -> {}",
+            h.op(),
+            describe(&h.old),
+            describe(&h.new)
+        )?;
+        if !h.old.is_empty() {
+            writeln!(out, "- {}", preview(&old[h.old.clone()]))?;
+        }
+        if !h.new.is_empty() {
+            writeln!(out, "+ {}", preview(&new[h.new.clone()]))?;
+        }
+    }
+    Ok(())
+}
+
+fn write_json(
+    old: &[u8],
+    new: &[u8],
+    hunks: &[Hunk],
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    writeln!(out, "[")?;
+    for (i, h) in hunks.iter().enumerate() {
+        writeln!(out, "  {{")?;
+        writeln!(out, "    \"op\": \"{}\",", h.op())?;
+        for (name, data, range) in &[("old", old, &h.old), ("new", new, &h.new)] {
+            writeln!(
+                out,
+                "    \"{}\": {}{}",
+                name,
+                json_range(range, data),
+                if *name == "old" { "," } else { "" }
+            )?;
+        }
+        writeln!(out, "  }}{}", if i + 1 < hunks.len() { "," } else { "" })?;
+    }
+    writeln!(out, "]")?;
+    Ok(())
+}
+
+impl crate::command::Command for DiffCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        let old = read_all(blocksize, source)?;
+        let new = read_all(blocksize, &mut open(&self.new_file, self.decompress)?)?;
+        let hunks = diff(&old, &new);
+
+        if self.json {
+            write_json(&old, &new, &hunks, out)?;
+        } else {
+            write_text(&old, &new, &hunks, out)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        let old = b"0123456789abcdef";
+        let new = b"01X23456789cdefYZ";
+        let hunks = diff(old, new);
+        let mut out: Vec<u8> = vec![];
+        assert!(write_text(old, new, &hunks, &mut out).is_ok());
+        assert_eq!(
+            std::str::from_utf8(&out).unwrap(),
+            "insert at 2 -> 2+1\n+ 58\n\
+             delete 10+2 -> at 11\n- 6162\n\
+             insert at 16 -> 15+2\n+ 595a\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let old = b"foobar";
+        let new = b"fooBAR";
+        let hunks = diff(old, new);
+        let mut out: Vec<u8> = vec![];
+        assert!(write_json(old, new, &hunks, &mut out).is_ok());
+        assert_eq!(
+            std::str::from_utf8(&out).unwrap(),
+            r#"[
+  {
+    "op": "replace",
+    "old": {"position": "3+3", "offset": 3, "length": 3, "data": "626172"},
+    "new": {"position": "3+3", "offset": 3, "length": 3, "data": "424152"}
+  }
+]
+"#
+        );
+    }
+
+    #[test]
+    fn test_json_empty() {
+        let old = b"foobar";
+        let new = b"fobar";
+        let hunks = diff(old, new);
+        let mut out: Vec<u8> = vec![];
+        assert!(write_json(old, new, &hunks, &mut out).is_ok());
+        assert_eq!(
+            std::str::from_utf8(&out).unwrap(),
+            r#"[
+  {
+    "op": "delete",
+    "old": {"position": "2+1", "offset": 2, "length": 1, "data": "6f"},
+    "new": {"offset": 2, "length": 0, "data": ""}
+  }
+]
+"#
+        );
+    }
+}
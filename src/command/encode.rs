@@ -0,0 +1,101 @@
+use crate::command::replace_range;
+use crate::encoding::Encoding;
+use crate::position::{Position, PositionParser};
+use clap::{value_t, ArgMatches};
+use failure::Error;
+
+/// Replaces a range by its text encoding or the decoded data of it.
+pub struct EncodeCommand {
+    encoding: Encoding,
+    decode: bool,
+    position: Position,
+}
+
+impl EncodeCommand {
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        Self::with_direction(m, parser, false)
+    }
+
+    pub fn from_decode_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        Self::with_direction(m, parser, true)
+    }
+
+    fn with_direction(
+        m: &ArgMatches,
+        parser: &PositionParser,
+        decode: bool,
+    ) -> Result<Self, Error> {
+        let encoding = value_t!(m, "encoding", String)?.parse::<Encoding>()?;
+        let position = match value_t!(m, "position", String) {
+            Ok(position) => parser.position(&position)?,
+            Err(_) => Position {
+                begin: 0,
+                end: None,
+            },
+        };
+        Ok(Self {
+            encoding,
+            decode,
+            position,
+        })
+    }
+}
+
+impl crate::command::Command for EncodeCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        replace_range(blocksize, source, out, &self.position, &mut |data| {
+            if self.decode {
+                self.encoding.decode(&data)
+            } else {
+                Ok(self.encoding.encode(&data).into_bytes())
+            }
+        })?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn test_encode() {
+        for bs in vec![1, 2, 3, 4, 10] {
+            for (encoding, decode, position, input, exp) in vec![
+                (Encoding::Base64, false, "2+3", "__foo__", "__Zm9v__"),
+                (Encoding::Base64, true, "2+4", "__Zm9v__", "__foo__"),
+                (Encoding::Hex, false, "2", "__foo", "__666f6f"),
+                (Encoding::Hex, true, "0+6", "666f6f__", "foo__"),
+                (Encoding::Base32, false, "0+2", "fo", "MZXQ===="),
+                (Encoding::Ascii85, true, "1", "_9jqo^", "_Man "),
+            ] {
+                let cmd = EncodeCommand {
+                    encoding,
+                    decode,
+                    position: position.parse().unwrap(),
+                };
+                let mut out: Vec<u8> = vec![];
+                assert!(cmd.run(bs, &mut input.as_bytes(), &mut out, None).is_ok());
+                assert_eq!(exp, std::str::from_utf8(&out).unwrap());
+            }
+        }
+
+        let cmd = EncodeCommand {
+            encoding: Encoding::Hex,
+            decode: true,
+            position: "0+3".parse().unwrap(),
+        };
+        let mut out: Vec<u8> = vec![];
+        assert!(cmd
+            .run(4, &mut "666f6f".as_bytes(), &mut out, None)
+            .is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::command::read_all;
+use crate::hash::Algorithm;
+use crate::position::{parse_number, Position, PositionParser};
+use clap::{value_t, ArgMatches};
+use failure::{bail, Error};
+use std::ops::Range;
+
+pub struct FixupCommand {
+    algo: Algorithm,
+    little_endian: bool,
+    over: String,
+    store: usize,
+    width: Option<usize>,
+}
+
+/// Splits an endianness suffix (`le`, `be`) from `algo`, big endian is the
+/// default.
+fn parse_algo(algo: &str) -> Result<(Algorithm, bool), Error> {
+    for (suffix, little_endian) in &[("le", true), ("be", false)] {
+        if let Some(name) = algo.strip_suffix(suffix) {
+            if let Ok(algo) = name.trim_end_matches('-').parse::<Algorithm>() {
+                return Ok((algo, *little_endian));
+            }
+        }
+    }
+    Ok((algo.parse::<Algorithm>()?, false))
+}
+
+/// Parses the covered range, a trailing `:` (e.g. `0x100:`) means up to the
+/// end of the input.
+fn parse_over(over: &str, len: usize) -> Result<Range<usize>, Error> {
+    let position = match over.strip_suffix(':') {
+        Some(begin) => Position {
+            begin: parse_number(begin)?,
+            end: None,
+        },
+        None => over.parse::<Position>()?,
+    };
+    position.range(len)
+}
+
+impl FixupCommand {
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        let (algo, little_endian) = match value_t!(m, "crc", String) {
+            Ok(crc) => (Algorithm::Crc(crc.parse()?), false),
+            Err(_) => parse_algo(&value_t!(m, "algo", String)?)?,
+        };
+        let little_endian = match value_t!(m, "endian", String) {
+            Ok(endian) => endian == "le",
+            Err(_) => little_endian,
+        };
+        let over = value_t!(m, "over", String)?;
+        let over = match over.strip_suffix(':') {
+            Some(begin) => format!("{}:", parser.offset(begin)?),
+            None => parser.resolve(&over)?,
+        };
+        let store = parse_number(&parser.offset(&value_t!(m, "store", String)?)?)?;
+        let width = match value_t!(m, "width", String) {
+            Ok(width) => Some(parse_number(&width)?),
+            Err(_) => None,
+        };
+        if width == Some(0) {
+            bail!("<width> has to be >= 1")
+        }
+        Ok(Self {
+            algo,
+            little_endian,
+            over,
+            store,
+            width,
+        })
+    }
+
+    /// Computes the checksum over `over` and stores it in `data`.
+    fn fixup(&self, data: &mut [u8]) -> Result<(), Error> {
+        let over = parse_over(&self.over, data.len())?;
+        let mut hasher = self.algo.hasher();
+        hasher.update(&data[over.clone()]);
+        let mut value = hasher.finish();
+
+        // truncate to the least significant bytes or pad with zeros
+        let width = self.width.unwrap_or(value.len());
+        if width < value.len() {
+            value.drain(0..value.len() - width);
+        } else {
+            value.splice(0..0, vec![0; width - value.len()]);
+        }
+        if self.little_endian {
+            value.reverse();
+        }
+
+        let store = self.store..self.store + width;
+        if store.end > data.len() {
+            bail!(
+                "--store {} exceeds the input of {} bytes",
+                self.store,
+                data.len()
+            )
+        }
+        if store.start < over.end && over.start < store.end {
+            bail!("The stored checksum must not lie within the --over range")
+        }
+        data[store].copy_from_slice(&value);
+        Ok(())
+    }
+}
+
+impl crate::command::Command for FixupCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        let mut data = read_all(blocksize, source)?;
+        self.fixup(&mut data)?;
+        out.write_all(&data)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn test_parse_algo() {
+        assert_eq!(parse_algo("crc32le").unwrap(), (Algorithm::Crc32, true));
+        assert_eq!(parse_algo("crc32be").unwrap(), (Algorithm::Crc32, false));
+        assert_eq!(parse_algo("crc32").unwrap(), (Algorithm::Crc32, false));
+        assert_eq!(
+            parse_algo("crc16-ccitt-le").unwrap(),
+            (Algorithm::Crc16Ccitt, true)
+        );
+        assert_eq!(parse_algo("sha256").unwrap(), (Algorithm::Sha256, false));
+        assert!(parse_algo("crc33le").is_err());
+    }
+
+    #[test]
+    fn test_fixup() {
+        let input = b"HDR_\0\0\0\x00123456789";
+        let mut cmd = FixupCommand {
+            algo: Algorithm::Crc32,
+            little_endian: true,
+            over: "0x8:".to_string(),
+            store: 4,
+            width: None,
+        };
+        for bs in vec![1, 2, 3, 4, 10, 1024] {
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(bs, &mut &input[..], &mut out, None).is_ok());
+            assert_eq!(out, b"HDR_\x26\x39\xf4\xcb123456789".to_vec());
+        }
+
+        cmd.little_endian = false;
+        cmd.width = Some(2);
+        cmd.over = "8+9".to_string();
+        let mut out: Vec<u8> = vec![];
+        assert!(cmd.run(4, &mut &input[..], &mut out, None).is_ok());
+        assert_eq!(out, b"HDR_\x39\x26\0\x00123456789".to_vec());
+
+        for (over, store) in vec![("0:", 4), ("8+10", 4), ("8:", 16), ("8:", 14)] {
+            cmd.over = over.to_string();
+            cmd.store = store;
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(4, &mut &input[..], &mut out, None).is_err());
+        }
+    }
+}
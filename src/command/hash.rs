@@ -0,0 +1,108 @@
+use crate::command::copy;
+use crate::encoding::hex_encode;
+use crate::hash::Algorithm;
+use crate::position::{Position, PositionParser};
+use clap::{value_t, ArgMatches};
+use failure::{bail, Error};
+
+pub struct HashCommand {
+    algo: Algorithm,
+    position: Option<String>,
+}
+
+impl HashCommand {
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        let algo = match value_t!(m, "crc", String) {
+            Ok(crc) => Algorithm::Crc(crc.parse()?),
+            Err(_) => value_t!(m, "algo", String)?.parse::<Algorithm>()?,
+        };
+        let position = match value_t!(m, "position", String) {
+            Ok(position) => Some(parser.resolve(&position)?),
+            Err(_) => None,
+        };
+        Ok(Self { algo, position })
+    }
+}
+
+/// Skips the bytes before `position` and feeds the range into a hasher of
+/// `algo`, returns the digest.
+pub(crate) fn hash_range(
+    algo: Algorithm,
+    position: &Position,
+    blocksize: usize,
+    source: &mut dyn std::io::Read,
+) -> Result<Vec<u8>, Error> {
+    if copy(
+        blocksize,
+        source,
+        &mut std::io::sink(),
+        Some(position.begin),
+    )? < position.begin
+    {
+        bail!("<POSITION> begins beyond the end of the input")
+    }
+    let mut hasher = algo.hasher();
+    let count = position.end.map(|end| end - position.begin + 1);
+    if copy(blocksize, source, &mut hasher, count)? < count.unwrap_or(0) {
+        bail!("<POSITION> ends beyond the end of the input")
+    }
+    Ok(hasher.finish())
+}
+
+impl crate::command::Command for HashCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        let position = match &self.position {
+            Some(p) => p.parse::<Position>()?,
+            None => Position {
+                begin: 0,
+                end: None,
+            },
+        };
+        let digest = hash_range(self.algo, &position, blocksize, source)?;
+        writeln!(out, "{}", hex_encode(&digest))?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn test_hash() {
+        let input = "__123456789__";
+        for bs in vec![1, 2, 3, 4, 10, 1024] {
+            for (position, exp) in vec![
+                (Some("2+9"), "cbf43926\n"),
+                (Some("2:11"), "cbf43926\n"),
+                (Some("13"), "00000000\n"),
+                (None, "1f130f8e\n"),
+            ] {
+                let cmd = HashCommand {
+                    algo: Algorithm::Crc32,
+                    position: position.map(|p| p.to_string()),
+                };
+                let mut out: Vec<u8> = vec![];
+                assert!(cmd.run(bs, &mut input.as_bytes(), &mut out, None).is_ok());
+                assert_eq!(exp, std::str::from_utf8(&out).unwrap());
+            }
+
+            for position in vec!["14", "2+12"] {
+                let cmd = HashCommand {
+                    algo: Algorithm::Crc32,
+                    position: Some(position.to_string()),
+                };
+                let mut out: Vec<u8> = vec![];
+                assert!(cmd.run(bs, &mut input.as_bytes(), &mut out, None).is_err());
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use crate::command::replace_range;
+use crate::position::{parse_number, Position, PositionParser};
+use clap::{value_t, ArgMatches};
+use failure::{bail, Error};
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+/// Container of a deflate stream.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Container {
+    Gzip,
+    Zlib,
+    Raw,
+}
+
+impl FromStr for Container {
+    type Err = Error;
+
+    fn from_str(container: &str) -> Result<Container, Error> {
+        Ok(match container {
+            "gzip" => Container::Gzip,
+            "zlib" => Container::Zlib,
+            "raw" => Container::Raw,
+            x => bail!("Unknown format: {}", x),
+        })
+    }
+}
+
+impl Container {
+    pub(crate) fn variants() -> &'static [&'static str] {
+        &["gzip", "zlib", "raw"]
+    }
+
+    /// Detects the container by the gzip magic or a valid zlib header,
+    /// anything else is assumed to be a raw deflate stream.
+    fn detect(data: &[u8]) -> Container {
+        match data {
+            [0x1f, 0x8b, ..] => Container::Gzip,
+            [cmf, flg, ..] if cmf & 0x0f == 8 && (*cmf as u16 * 256 + *flg as u16) % 31 == 0 => {
+                Container::Zlib
+            }
+            _ => Container::Raw,
+        }
+    }
+
+    /// Decompresses the stream at the beginning of `data`, returns the
+    /// decompressed data and the number of bytes the stream occupied.
+    fn decompress(self, data: &[u8]) -> Result<(Vec<u8>, usize), Error> {
+        let mut decompressed = vec![];
+        let rest = match self {
+            Container::Gzip => {
+                let mut d = flate2::bufread::GzDecoder::new(data);
+                d.read_to_end(&mut decompressed)?;
+                d.into_inner()
+            }
+            Container::Zlib => {
+                let mut d = flate2::bufread::ZlibDecoder::new(data);
+                d.read_to_end(&mut decompressed)?;
+                d.into_inner()
+            }
+            Container::Raw => {
+                let mut d = flate2::bufread::DeflateDecoder::new(data);
+                d.read_to_end(&mut decompressed)?;
+                d.into_inner()
+            }
+        };
+        Ok((decompressed, data.len() - rest.len()))
+    }
+
+    fn compress(self, data: &[u8], level: u32) -> Result<Vec<u8>, Error> {
+        let level = flate2::Compression::new(level);
+        Ok(match self {
+            Container::Gzip => {
+                let mut e = flate2::write::GzEncoder::new(vec![], level);
+                e.write_all(data)?;
+                e.finish()?
+            }
+            Container::Zlib => {
+                let mut e = flate2::write::ZlibEncoder::new(vec![], level);
+                e.write_all(data)?;
+                e.finish()?
+            }
+            Container::Raw => {
+                let mut e = flate2::write::DeflateEncoder::new(vec![], level);
+                e.write_all(data)?;
+                e.finish()?
+            }
+        })
+    }
+}
+
+/// Replaces a compressed range by the decompressed data (`inflate`) or the
+/// other way round (`deflate`).
+pub struct InflateCommand {
+    container: Option<Container>,
+    deflate: bool,
+    level: u32,
+    position: Position,
+}
+
+impl InflateCommand {
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        Self::with_direction(m, parser, false)
+    }
+
+    pub fn from_deflate_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        Self::with_direction(m, parser, true)
+    }
+
+    fn with_direction(
+        m: &ArgMatches,
+        parser: &PositionParser,
+        deflate: bool,
+    ) -> Result<Self, Error> {
+        let container = match value_t!(m, "format", String) {
+            Ok(container) => Some(container.parse::<Container>()?),
+            Err(_) => None,
+        };
+        let level = match value_t!(m, "level", String) {
+            Ok(level) => parse_number(&level)?,
+            Err(_) => 6,
+        };
+        if level > 9 {
+            bail!("<level> has to be between 0 and 9")
+        }
+        let position = parser.position(&value_t!(m, "position", String)?)?;
+        Ok(Self {
+            container,
+            deflate,
+            level: level as u32,
+            position,
+        })
+    }
+
+    /// Decompresses the stream at the beginning of `data`, bytes behind the
+    /// stream are kept.
+    fn inflate(&self, data: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let container = self.container.unwrap_or_else(|| Container::detect(&data));
+        let (mut decompressed, len) = container.decompress(&data)?;
+        decompressed.extend_from_slice(&data[len..]);
+        Ok(decompressed)
+    }
+}
+
+impl crate::command::Command for InflateCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        replace_range(blocksize, source, out, &self.position, &mut |data| {
+            if self.deflate {
+                let container = self.container.unwrap_or(Container::Zlib);
+                container.compress(&data, self.level)
+            } else {
+                self.inflate(data)
+            }
+        })?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn decompress_input(&self) -> bool {
+        // the compressed streams are the data to work on
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Container::detect(b"\x1f\x8b\x08"), Container::Gzip);
+        assert_eq!(Container::detect(b"\x78\x9c"), Container::Zlib);
+        assert_eq!(Container::detect(b"\x78\xda"), Container::Zlib);
+        assert_eq!(Container::detect(b"\x78\x9d"), Container::Raw);
+        assert_eq!(Container::detect(b"\x78"), Container::Raw);
+    }
+
+    #[test]
+    fn test_inflate() {
+        let text = "Hallo Welt, Hallo Welt, Hallo Welt!";
+        for container in vec![Container::Gzip, Container::Zlib, Container::Raw] {
+            let deflate = InflateCommand {
+                container: Some(container),
+                deflate: true,
+                level: 9,
+                position: "4+35".parse().unwrap(),
+            };
+            let input = format!("HDR_{}\x00\x00", text);
+            let mut compressed: Vec<u8> = vec![];
+            assert!(deflate
+                .run(4, &mut input.as_bytes(), &mut compressed, None)
+                .is_ok());
+            assert_eq!(&compressed[..4], b"HDR_");
+            assert_eq!(&compressed[compressed.len() - 2..], b"\0\0");
+            assert_ne!(compressed, input.as_bytes());
+
+            for (detect, position) in vec![(true, "4"), (false, "4"), (false, "4+8")] {
+                let inflate = InflateCommand {
+                    container: if detect && container != Container::Raw {
+                        None
+                    } else {
+                        Some(container)
+                    },
+                    deflate: false,
+                    level: 6,
+                    position: position.parse().unwrap(),
+                };
+                for bs in vec![1, 2, 3, 4, 10, 1024] {
+                    let mut out: Vec<u8> = vec![];
+                    let result = inflate.run(bs, &mut &compressed[..], &mut out, None);
+                    if position == "4" {
+                        assert!(result.is_ok());
+                        assert_eq!(out, input.as_bytes());
+                    } else {
+                        assert!(result.is_err());
+                    }
+                }
+            }
+        }
+    }
+}
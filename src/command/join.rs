@@ -0,0 +1,204 @@
+use crate::command::{copy, open, parse_bytes, read_all, spool, unspool};
+use crate::defs;
+use crate::position::{parse_number, PositionParser};
+use clap::{value_t, values_t, ArgMatches};
+use failure::{bail, Error};
+
+pub struct JoinCommand {
+    /// `FILE` or `FILE@POSITION` of the parts after the input.
+    parts: Vec<String>,
+    separator: Vec<u8>,
+    align: Option<usize>,
+    fill: u8,
+    decompress: bool,
+}
+
+/// Splits `FILE@POSITION` into its parts, the position is optional.
+fn split_part(part: &str) -> (&str, Option<&str>) {
+    match part.rfind('@') {
+        Some(i) if !std::path::Path::new(part).exists() => (&part[..i], Some(&part[i + 1..])),
+        _ => (part, None),
+    }
+}
+
+impl JoinCommand {
+    pub fn from_matches(m: &ArgMatches) -> Result<Self, Error> {
+        let parts = values_t!(m, "parts", String)?;
+        let separator = match value_t!(m, "separator", String) {
+            Ok(separator) => parse_bytes(&separator)?,
+            Err(_) => vec![],
+        };
+        let align = match value_t!(m, "align", String) {
+            Ok(align) => Some(parse_number(&align)?),
+            Err(_) => None,
+        };
+        if align == Some(0) {
+            bail!("<align> has to be >= 1")
+        }
+        let fill = value_t!(m, "fill", String).unwrap_or_else(|_| "0".to_string());
+        let fill = parse_number(&fill)?;
+        if fill > 0xff {
+            bail!("<fill> has to be a byte value")
+        }
+        let decompress = !m.is_present(defs::NO_DECOMPRESS_P);
+        Ok(Self {
+            parts,
+            separator,
+            align,
+            fill: fill as u8,
+            decompress,
+        })
+    }
+
+    /// Writes the separator (except after the last part) and the padding
+    /// to the next aligned offset.
+    fn finish_part(
+        &self,
+        last: bool,
+        offset: &mut usize,
+        out: &mut dyn std::io::Write,
+    ) -> Result<(), Error> {
+        if !last {
+            out.write_all(&self.separator)?;
+            *offset += self.separator.len();
+        }
+        if let Some(align) = self.align {
+            let pad = (align - *offset % align) % align;
+            out.write_all(&vec![self.fill; pad])?;
+            *offset += pad;
+        }
+        Ok(())
+    }
+}
+
+impl crate::command::Command for JoinCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        // read the parts first to fail before anything is written
+        let mut parts = vec![];
+        for part in &self.parts {
+            let (file, position) = split_part(part);
+            let position = match position {
+                // ELF references refer to the part itself
+                Some(p) => Some(
+                    PositionParser::new(|| read_all(blocksize, &mut open(file, self.decompress)?))
+                        .position(p)?,
+                ),
+                None => None,
+            };
+            let mut f = open(file, self.decompress)?;
+            let mut data = spool();
+            let len = match position {
+                Some(p) => {
+                    let skipped = copy(blocksize, &mut f, &mut std::io::sink(), Some(p.begin))?;
+                    if skipped < p.begin {
+                        bail!("{} begins beyond the end of the file", part)
+                    }
+                    let count = p.end.map(|end| end - p.begin + 1);
+                    let n = copy(blocksize, &mut f, &mut data, count)?;
+                    if n < count.unwrap_or(0) {
+                        bail!("{} ends beyond the end of the file", part)
+                    }
+                    n
+                }
+                None => copy(blocksize, &mut f, &mut data, None)?,
+            };
+            parts.push((data, len));
+        }
+
+        let mut offset = copy(blocksize, source, out, None)?;
+        self.finish_part(parts.is_empty(), &mut offset, out)?;
+        let count = parts.len();
+        for (i, (data, len)) in parts.into_iter().enumerate() {
+            unspool(blocksize, data, out)?;
+            offset += len;
+            self.finish_part(i + 1 == count, &mut offset, out)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn test_join() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a").to_str().unwrap().to_string();
+        let b = dir.path().join("b@1").to_str().unwrap().to_string();
+        std::fs::write(&a, "Welt").unwrap();
+        std::fs::write(&b, "!?").unwrap();
+
+        let mut cmd = JoinCommand {
+            parts: vec![a.clone(), b.clone(), format!("{}@1+2", a)],
+            separator: vec![],
+            align: None,
+            fill: 0,
+            decompress: true,
+        };
+        let input = "Hallo";
+        for bs in vec![1, 2, 3, 4, 10] {
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(bs, &mut input.as_bytes(), &mut out, None).is_ok());
+            assert_eq!(out, b"HalloWelt!?el".to_vec());
+        }
+
+        cmd.separator = b"--".to_vec();
+        cmd.align = Some(4);
+        cmd.fill = 0xff;
+        let mut out: Vec<u8> = vec![];
+        assert!(cmd.run(3, &mut input.as_bytes(), &mut out, None).is_ok());
+        assert_eq!(out, b"Hallo--\xffWelt--\xff\xff!?--el\xff\xff".to_vec());
+
+        for part in vec![
+            format!("{}@5", a),
+            format!("{}@2+3", a),
+            format!("{}.missing", a),
+        ] {
+            cmd.parts = vec![a.clone(), part];
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(3, &mut input.as_bytes(), &mut out, None).is_err());
+            assert!(out.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_separator() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a").to_str().unwrap().to_string();
+        std::fs::write(&a, "Welt").unwrap();
+
+        for (separator, exp) in vec![
+            ("hex:00ff0a", Some(&b"Hallo\x00\xff\nWelt"[..])),
+            ("str:, ", Some(&b"Hallo, Welt"[..])),
+            ("00", None),
+            ("hex:0", None),
+        ] {
+            let args: Vec<std::ffi::OsString> = vec!["bytie", "join", &a, "--separator", separator]
+                .into_iter()
+                .map(|a| a.into())
+                .collect();
+            let m = crate::cli::get_matches_from(&args);
+            let cmd = JoinCommand::from_matches(m.subcommand_matches("join").unwrap());
+            match exp {
+                Some(exp) => {
+                    for bs in vec![1, 2, 3, 4, 10] {
+                        let mut out: Vec<u8> = vec![];
+                        let cmd = cmd.as_ref().unwrap();
+                        assert!(cmd.run(bs, &mut &b"Hallo"[..], &mut out, None).is_ok());
+                        assert_eq!(out, exp.to_vec());
+                    }
+                }
+                None => assert!(cmd.is_err()),
+            }
+        }
+    }
+}
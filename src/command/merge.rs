@@ -0,0 +1,274 @@
+use crate::command::diff::json_range;
+use crate::command::{open, read_all};
+use crate::defs;
+use crate::diff::{diff, Hunk};
+use crate::position::describe;
+use clap::{value_t, ArgMatches};
+use failure::{bail, Error};
+use std::ops::Range;
+
+pub struct MergeCommand {
+    ours_file: String,
+    theirs_file: String,
+    report: Option<String>,
+    decompress: bool,
+}
+
+impl MergeCommand {
+    pub fn from_matches(m: &ArgMatches) -> Result<Self, Error> {
+        let ours_file = value_t!(m, "ours", String)?;
+        let theirs_file = value_t!(m, "theirs", String)?;
+        let report = value_t!(m, "report", String).ok();
+        let decompress = !m.is_present(defs::NO_DECOMPRESS_P);
+        Ok(Self {
+            ours_file,
+            theirs_file,
+            report,
+            decompress,
+        })
+    }
+}
+
+/// Overlapping changes of both sides, the ranges refer to the base, ours and
+/// theirs data.
+#[derive(Debug, PartialEq, Eq)]
+struct Conflict {
+    base: Range<usize>,
+    ours: Range<usize>,
+    theirs: Range<usize>,
+}
+
+/// Base range of hunks which overlap or touch each other, with the side (0
+/// for ours, 1 for theirs) of each hunk.
+type Cluster = (Range<usize>, Vec<(usize, Hunk)>);
+
+/// Maps the base `range` onto the data of one side, `first` and `last` are
+/// the hunks of this side which lie within `range`.
+fn side_range(range: &Range<usize>, first: &Hunk, last: &Hunk) -> Range<usize> {
+    first.new.start - (first.old.start - range.start)..last.new.end + (range.end - last.old.end)
+}
+
+/// Merges the changes of `ours` and `theirs` relative to `base`. Changes of
+/// both sides which overlap or touch are conflicts unless they are identical.
+fn merge(base: &[u8], ours: &[u8], theirs: &[u8]) -> Result<Vec<u8>, Vec<Conflict>> {
+    let mut hunks: Vec<(usize, Hunk)> = diff(base, ours)
+        .into_iter()
+        .map(|h| (0, h))
+        .chain(diff(base, theirs).into_iter().map(|h| (1, h)))
+        .collect();
+    hunks.sort_by_key(|(_, h)| (h.old.start, h.old.end));
+    let data = [ours, theirs];
+
+    let mut clusters: Vec<Cluster> = vec![];
+    for (side, h) in hunks {
+        match clusters.last_mut() {
+            Some((range, c)) if h.old.start <= range.end => {
+                range.end = std::cmp::max(range.end, h.old.end);
+                c.push((side, h));
+            }
+            _ => clusters.push((h.old.clone(), vec![(side, h)])),
+        }
+    }
+
+    let mut edits: Vec<(Range<usize>, &[u8])> = vec![];
+    let mut conflicts: Vec<Conflict> = vec![];
+    for (range, c) in clusters {
+        let identical = c.len() == 2
+            && c[0].1.old == c[1].1.old
+            && data[c[0].0][c[0].1.new.clone()] == data[c[1].0][c[1].1.new.clone()];
+        if identical || c.iter().all(|(side, _)| *side == c[0].0) {
+            for (side, h) in c.iter().take(if identical { 1 } else { c.len() }) {
+                edits.push((h.old.clone(), &data[*side][h.new.clone()]));
+            }
+            continue;
+        }
+
+        let side = |s: usize| {
+            let mut it = c.iter().filter(|(side, _)| *side == s).map(|(_, h)| h);
+            let first = it.next().unwrap();
+            side_range(&range, first, it.next_back().unwrap_or(first))
+        };
+        conflicts.push(Conflict {
+            base: range.clone(),
+            ours: side(0),
+            theirs: side(1),
+        });
+    }
+    if !conflicts.is_empty() {
+        return Err(conflicts);
+    }
+
+    let mut merged = vec![];
+    let mut pos = 0;
+    for (range, data) in edits {
+        merged.extend_from_slice(&base[pos..range.start]);
+        merged.extend_from_slice(data);
+        pos = range.end;
+    }
+    merged.extend_from_slice(&base[pos..]);
+    Ok(merged)
+}
+
+fn write_report(
+    data: [&[u8]; 3],
+    conflicts: &[Conflict],
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    writeln!(out, "[")?;
+    for (i, c) in conflicts.iter().enumerate() {
+        writeln!(out, "  {{")?;
+        let ranges = [("base", &c.base), ("ours", &c.ours), ("theirs", &c.theirs)];
+        for (j, (name, range)) in ranges.iter().enumerate() {
+            writeln!(
+                out,
+                "    \"{}\": {}{}",
+                name,
+                json_range(range, data[j]),
+                if j + 1 < ranges.len() { "," } else { "" }
+            )?;
+        }
+        writeln!(
+            out,
+            "  }}{}",
+            if i + 1 < conflicts.len() { "," } else { "" }
+        )?;
+    }
+    writeln!(out, "]")?;
+    Ok(())
+}
+
+impl crate::command::Command for MergeCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        let base = read_all(blocksize, source)?;
+        let ours = read_all(blocksize, &mut open(&self.ours_file, self.decompress)?)?;
+        let theirs = read_all(blocksize, &mut open(&self.theirs_file, self.decompress)?)?;
+
+        let result = merge(&base, &ours, &theirs);
+        if let Some(report) = &self.report {
+            let conflicts = match &result {
+                Ok(_) => &[][..],
+                Err(conflicts) => &conflicts[..],
+            };
+            let mut file = std::fs::File::create(report)?;
+            write_report([&base, &ours, &theirs], conflicts, &mut file)?;
+        }
+
+        match result {
+            Ok(merged) => {
+                out.write_all(&merged)?;
+                out.flush()?;
+                Ok(())
+            }
+            Err(conflicts) => {
+                let list: Vec<String> = conflicts
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "base {}, ours {}, theirs {}",
+                            describe(&c.base),
+                            describe(&c.ours),
+                            describe(&c.theirs)
+                        )
+                    })
+                    .collect();
+                bail!(
+                    "Merge failed with {} conflict(s):\n{}",
+                    conflicts.len(),
+                    list.join("\n")
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let base = b"0123456789abcdefghijklmnopqrstuvwxyz";
+        let ours = b"0123XY456789abcdefghijklmnopqrstuvwxyz";
+        let theirs = b"0123456789abcdefghijklmnopqrst";
+        assert_eq!(
+            merge(base, ours, theirs).unwrap(),
+            b"0123XY456789abcdefghijklmnopqrst".to_vec()
+        );
+
+        // identical changes on both sides
+        let ours = b"0123456789ABCdefghijklmnopqrstuvwxyz";
+        assert_eq!(merge(base, ours, ours).unwrap(), ours.to_vec());
+        assert_eq!(merge(base, base, ours).unwrap(), ours.to_vec());
+    }
+
+    #[test]
+    fn test_conflict() {
+        let base = b"0123456789abcdefghijklmnopqrstuvwxyz";
+        let ours = b"0123456789ABCdefghijklmnopqrstuvwxyz";
+        let theirs = b"0123456789aBcdefghijklmnopqrstuvwxyZ";
+        assert_eq!(
+            merge(base, ours, theirs).unwrap_err(),
+            vec![Conflict {
+                base: 10..13,
+                ours: 10..13,
+                theirs: 10..13,
+            }]
+        );
+
+        // insertions at the same position
+        let ours = b"01234567X89abcdefghijklmnopqrstuvwxyz";
+        let theirs = b"01234567YY89abcdefghijklmnopqrstuvwxyz";
+        assert_eq!(
+            merge(base, ours, theirs).unwrap_err(),
+            vec![Conflict {
+                base: 8..8,
+                ours: 8..9,
+                theirs: 8..10,
+            }]
+        );
+
+        // insertion at the begin of a replaced range
+        let theirs = b"01234567YY9abcdefghijklmnopqrstuvwxyz";
+        assert!(merge(base, ours, theirs).is_err());
+
+        // adjacent replacements
+        let ours = b"0123456789ABCdefghijklmnopqrstuvwxyz";
+        let theirs = b"0123456789abcDEfghijklmnopqrstuvwxyz";
+        assert_eq!(
+            merge(base, ours, theirs).unwrap_err(),
+            vec![Conflict {
+                base: 10..15,
+                ours: 10..15,
+                theirs: 10..15,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let conflicts = vec![Conflict {
+            base: 1..2,
+            ours: 1..3,
+            theirs: 1..1,
+        }];
+        let mut out: Vec<u8> = vec![];
+        assert!(write_report([b"abc", b"aXYc", b"ac"], &conflicts, &mut out).is_ok());
+        assert_eq!(
+            std::str::from_utf8(&out).unwrap(),
+            r#"[
+  {
+    "base": {"position": "1+1", "offset": 1, "length": 1, "data": "62"},
+    "ours": {"position": "1+2", "offset": 1, "length": 2, "data": "5859"},
+    "theirs": {"offset": 1, "length": 0, "data": ""}
+  }
+]
+"#
+        );
+    }
+}
@@ -1,11 +1,32 @@
 pub mod add;
+pub mod bitwise;
+pub mod copy;
 pub mod cut;
 pub mod delete;
+pub mod delta;
+pub mod diff;
+pub mod encode;
+pub mod fixup;
+pub mod hash;
+pub mod inflate;
+pub mod join;
+pub mod merge;
+pub mod overlay;
+pub mod patch;
 pub mod replace;
+pub mod split;
+pub mod swap;
+pub mod transform;
+use crate::archive::Archive;
+use crate::compression::Compression;
 use crate::defs;
+use crate::encoding::hex_decode;
+use crate::hexdump;
+use crate::position::Position;
 use clap::{value_t, ArgMatches};
 use failure::{bail, Error};
 use std::fs::OpenOptions;
+use std::io::{Seek, Write};
 
 pub trait Command {
     fn run(
@@ -15,73 +36,631 @@ pub trait Command {
         out: &mut dyn std::io::Write,
         input: Option<&mut dyn std::io::Read>,
     ) -> Result<(), Error>;
+
+    /// Whether a compressed input is decompressed transparently, commands
+    /// which work on the compressed bytes themselves return `false`.
+    fn decompress_input(&self) -> bool {
+        true
+    }
+
+    /// Whether the output is the edited input, which may replace the input
+    /// file in-place. Commands which write files and print their names
+    /// return `false`.
+    fn edits_input(&self) -> bool {
+        true
+    }
+}
+
+/// Copies `limit` bytes (or everything if `None`) from `source` to `out`.
+/// Returns the number of bytes copied, which is less than `limit` if the
+/// source ended before.
+pub(crate) fn copy(
+    blocksize: usize,
+    source: &mut dyn std::io::Read,
+    out: &mut dyn std::io::Write,
+    limit: Option<usize>,
+) -> Result<usize, Error> {
+    let mut buffer = vec![0; blocksize];
+    let mut total = 0;
+    loop {
+        let want = match limit {
+            Some(limit) => std::cmp::min(limit - total, blocksize),
+            None => blocksize,
+        };
+        if want == 0 {
+            break;
+        }
+        let n = source.read(&mut buffer[0..want])?;
+        if n == 0 {
+            break;
+        }
+        out.write_all(&buffer[0..n])?;
+        total += n;
+    }
+    Ok(total)
+}
+
+/// Size up to which held back output is kept in memory, larger output is
+/// spooled to a temporary file.
+static SPOOL_SIZE: usize = 16 * 1024 * 1024;
+
+/// Creates a buffer for output which must not reach the real output before
+/// the input has been validated, see `unspool`.
+pub(crate) fn spool() -> tempfile::SpooledTempFile {
+    tempfile::spooled_tempfile(SPOOL_SIZE)
+}
+
+/// Copies the output held back in `spool` to `out`.
+pub(crate) fn unspool(
+    blocksize: usize,
+    mut spool: tempfile::SpooledTempFile,
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    spool.seek(std::io::SeekFrom::Start(0))?;
+    copy(blocksize, &mut spool, out, None)?;
+    Ok(())
+}
+
+/// Opens the file `path` which a command uses besides the input, it is
+/// decompressed like the input if `decompress` is set.
+pub(crate) fn open(path: &str, decompress: bool) -> Result<Box<dyn std::io::Read>, Error> {
+    let f: Box<dyn std::io::Read> = Box::new(std::fs::File::open(path)?);
+    if decompress {
+        Ok(Compression::detect_reader(f)?.0)
+    } else {
+        Ok(f)
+    }
+}
+
+/// Reads the complete `source` into memory.
+pub(crate) fn read_all(blocksize: usize, source: &mut dyn std::io::Read) -> Result<Vec<u8>, Error> {
+    let mut data = vec![];
+    copy(blocksize, source, &mut data, None)?;
+    Ok(data)
+}
+
+/// Copies `source` to `out` and applies `f` to the bytes of `position`. The
+/// range is passed in pieces which are a multiple of `unit` bytes, even if a
+/// unit straddles the blocks read from `source`. Nothing is written to `out`
+/// if the range doesn't fit the input.
+pub(crate) fn map_range(
+    blocksize: usize,
+    source: &mut dyn std::io::Read,
+    out: &mut dyn std::io::Write,
+    position: &Position,
+    unit: usize,
+    f: &mut dyn FnMut(&mut [u8]),
+) -> Result<(), Error> {
+    let count = position.end.map(|end| end - position.begin + 1);
+    if let Some(count) = count {
+        if count % unit != 0 {
+            bail!(
+                "The length of <POSITION> is not a multiple of {} bytes",
+                unit
+            )
+        }
+    }
+    let mut head = spool();
+    if copy(blocksize, source, &mut head, Some(position.begin))? < position.begin {
+        bail!("<POSITION> begins beyond the end of the input")
+    }
+    let mut pending = vec![];
+    let mut total = 0;
+    loop {
+        let want = match count {
+            Some(count) => std::cmp::min(count - total, blocksize),
+            None => blocksize,
+        };
+        let n = copy(blocksize, source, &mut pending, Some(want))?;
+        if n == 0 {
+            break;
+        }
+        total += n;
+        let complete = pending.len() - pending.len() % unit;
+        f(&mut pending[..complete]);
+        head.write_all(&pending[..complete])?;
+        pending.drain(..complete);
+    }
+    if total < count.unwrap_or(0) {
+        bail!("<POSITION> ends beyond the end of the input")
+    }
+    if !pending.is_empty() {
+        bail!(
+            "The length of the range is not a multiple of {} bytes",
+            unit
+        )
+    }
+    unspool(blocksize, head, out)?;
+    copy(blocksize, source, out, None)?;
+    Ok(())
+}
+
+/// Copies `source` to `out` and replaces the bytes of `position` by the
+/// result of `f`, which receives the complete range. Nothing is written to
+/// `out` if the range doesn't fit the input or `f` fails.
+pub(crate) fn replace_range(
+    blocksize: usize,
+    source: &mut dyn std::io::Read,
+    out: &mut dyn std::io::Write,
+    position: &Position,
+    f: &mut dyn FnMut(Vec<u8>) -> Result<Vec<u8>, Error>,
+) -> Result<(), Error> {
+    let mut head = spool();
+    if copy(blocksize, source, &mut head, Some(position.begin))? < position.begin {
+        bail!("<POSITION> begins beyond the end of the input")
+    }
+    let count = position.end.map(|end| end - position.begin + 1);
+    let mut data = vec![];
+    if copy(blocksize, source, &mut data, count)? < count.unwrap_or(0) {
+        bail!("<POSITION> ends beyond the end of the input")
+    }
+    let data = f(data)?;
+    unspool(blocksize, head, out)?;
+    out.write_all(&data)?;
+    copy(blocksize, source, out, None)?;
+    Ok(())
+}
+
+/// Parses a byte value given as `hex:<HEX DIGITS>` or `str:<STRING>`.
+pub(crate) fn parse_bytes(value: &str) -> Result<Vec<u8>, Error> {
+    if let Some(digits) = value.strip_prefix("hex:") {
+        hex_decode(digits)
+    } else if let Some(string) = value.strip_prefix("str:") {
+        Ok(string.as_bytes().to_vec())
+    } else {
+        bail!("'{}' requires a 'hex:' or 'str:' prefix", value)
+    }
+}
+
+/// Reads a `xxd` or `hexdump -C` text dump from `source` and converts it back
+/// to binary data.
+pub(crate) fn read_hexdump(
+    blocksize: usize,
+    source: &mut dyn std::io::Read,
+) -> Result<Vec<hexdump::Chunk>, Error> {
+    let text = String::from_utf8(read_all(blocksize, source)?)?;
+    hexdump::parse(&text)
+}
+
+/// Reads the hex dump which is provided either by `input` or by `value`.
+pub(crate) fn read_hexdump_value(
+    blocksize: usize,
+    value: Option<&Vec<u8>>,
+    input: Option<&mut dyn std::io::Read>,
+) -> Result<Vec<hexdump::Chunk>, Error> {
+    if let Some(input) = input {
+        read_hexdump(blocksize, input)
+    } else if let Some(value) = value {
+        hexdump::parse(std::str::from_utf8(value)?)
+    } else {
+        bail!("No STDIN nor any <VALUE> has been provided, unable to read dump.")
+    }
+}
+
+/// Writes to `out` using `f`, compressed with `compression` if set.
+fn write_compressed<F>(
+    out: &mut dyn std::io::Write,
+    compression: Option<Compression>,
+    f: F,
+) -> Result<(), Error>
+where
+    F: FnOnce(&mut dyn std::io::Write) -> Result<(), Error>,
+{
+    if let Some(compression) = compression {
+        let mut encoder = compression.encoder(out)?;
+        f(&mut encoder)?;
+        encoder.finish()?.flush()?;
+    } else {
+        f(out)?;
+    }
+    Ok(())
+}
+
+/// Splits `archive.zip!path/in/zip` into the archive and the member path,
+/// unless a file with the whole name exists.
+fn split_member(file: &str) -> Option<(String, String)> {
+    if std::path::Path::new(file).exists() {
+        return None;
+    }
+    let i = file.rfind('!')?;
+    if std::path::Path::new(&file[..i]).is_file() {
+        Some((file[..i].to_string(), file[i + 1..].to_string()))
+    } else {
+        None
+    }
 }
 
 pub struct CommandRunner {
     blocksize: usize,
     in_place: bool,
+    hexdump: bool,
     out_file: Option<String>,
     in_file: Option<String>,
+    compress: Option<Compression>,
+    decompress: bool,
+    member: Option<String>,
+    extract: bool,
 }
 
 impl CommandRunner {
     pub fn from_matches(matches: &ArgMatches) -> Result<Self, Error> {
         let blocksize = value_t!(matches, defs::BLOCKSIZE_P, usize).unwrap_or(defs::BLOCKSIZE);
         let in_place = matches.is_present(defs::IN_PLACE_P);
+        let hexdump = matches.is_present(defs::HEXDUMP_P);
         let out_file = value_t!(matches, defs::OUTPUT_P, String).ok();
-        let in_file = value_t!(matches, "file", String).ok();
+        let mut in_file = value_t!(matches, "file", String).ok();
+        let compress = match value_t!(matches, defs::COMPRESS_P, String) {
+            Ok(compress) => Some(compress.parse::<Compression>()?),
+            Err(_) => None,
+        };
+        let decompress = !matches.is_present(defs::NO_DECOMPRESS_P);
+        let mut member = value_t!(matches, defs::MEMBER_P, String).ok();
+        if let Some((file, path)) = in_file.as_deref().and_then(split_member) {
+            if member.is_some() {
+                bail!("The member is given twice: {}!{}", file, path);
+            }
+            member = Some(path);
+            in_file = Some(file);
+        }
+        let extract = matches.is_present(defs::EXTRACT_P);
+        if extract && member.is_none() {
+            bail!("--extract requires an archive member");
+        }
 
         Ok(CommandRunner {
             blocksize,
             in_place,
+            hexdump,
             out_file,
             in_file,
+            compress,
+            decompress,
+            member,
+            extract,
         })
     }
 
-    fn exec_impl(
-        &self,
-        src: &mut dyn std::io::Read,
-        input: Option<&mut dyn std::io::Read>,
-        command: &mut impl Command,
-    ) -> Result<(), Error> {
+    /// Writes the output using `f` to the output file, the input file (in-place)
+    /// or STDOUT. It is compressed if requested, for in-place editing with
+    /// `in_compression` of the input by default.
+    fn write_output<F>(&self, in_compression: Option<Compression>, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut dyn std::io::Write) -> Result<(), Error>,
+    {
         if let Some(ref fname) = self.out_file {
-            let mut f = OpenOptions::new().write(true).create(true).open(fname)?;
-            command.run(self.blocksize, src, &mut f, input)?;
+            // the output file is only touched if the command succeeded
+            let mut tmp_f = tempfile::tempfile()?;
+            let compression = self.compress.or_else(|| Compression::from_path(fname));
+            write_compressed(&mut tmp_f, compression, f)?;
+            tmp_f.seek(std::io::SeekFrom::Start(0))?;
+            let mut f_out = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(fname)?;
+            copy(self.blocksize, &mut tmp_f, &mut f_out, None)?;
         } else if self.in_place {
             if let Some(ref file) = self.in_file {
                 let mut tmp_f = tempfile::NamedTempFile::new()?;
-                command.run(self.blocksize, src, &mut tmp_f, input)?;
+                write_compressed(&mut tmp_f, self.compress.or(in_compression), f)?;
                 std::fs::copy(tmp_f, file)?;
             } else {
                 bail!("'in-place' requires an input file");
             }
         } else {
-            command.run(self.blocksize, src, &mut std::io::stdout(), input)?;
+            write_compressed(&mut std::io::stdout(), self.compress, f)?;
         }
 
         Ok(())
     }
 
+    fn exec_impl(
+        &self,
+        src: &mut dyn std::io::Read,
+        input: Option<&mut dyn std::io::Read>,
+        command: &mut impl Command,
+        in_compression: Option<Compression>,
+    ) -> Result<(), Error> {
+        self.write_output(in_compression, |out| {
+            command.run(self.blocksize, src, out, input)
+        })
+    }
+
+    /// Runs `command` on the content of `member` of the archive `src`. The
+    /// archive is written with the output as new content of the member, or
+    /// with `--extract` the output is written as is.
+    fn exec_member(
+        &self,
+        src: &mut dyn std::io::Read,
+        member: &str,
+        input: Option<&mut dyn std::io::Read>,
+        command: &mut impl Command,
+        in_compression: Option<Compression>,
+    ) -> Result<(), Error> {
+        let archive = Archive::parse(read_all(self.blocksize, src)?)?;
+        let content = archive.member(member)?;
+        if self.extract {
+            self.exec_impl(&mut content.as_slice(), input, command, None)
+        } else {
+            let mut data = vec![];
+            command.run(self.blocksize, &mut content.as_slice(), &mut data, input)?;
+            let data = archive.replace(member, &data)?;
+            self.write_output(in_compression, |out| Ok(out.write_all(&data)?))
+        }
+    }
+
+    /// Reads the whole input as the subcommand would see it, e.g. to resolve
+    /// ELF references in the positions of the subcommand.
+    pub fn read_input(&self) -> Result<Vec<u8>, Error> {
+        let file = match &self.in_file {
+            Some(file) => file,
+            None => bail!("ELF references in positions require an input file"),
+        };
+        let src: Box<dyn std::io::Read> = Box::new(std::fs::File::open(file)?);
+        let (mut src, _) = if self.decompress {
+            Compression::detect_reader(src)?
+        } else {
+            (src, None)
+        };
+        if let Some(member) = &self.member {
+            Archive::parse(read_all(self.blocksize, &mut src)?)?.member(member)
+        } else if self.hexdump {
+            Ok(hexdump::to_bytes(&read_hexdump(self.blocksize, &mut src)?))
+        } else {
+            read_all(self.blocksize, &mut src)
+        }
+    }
+
+    fn exec_source(
+        &self,
+        src: Box<dyn std::io::Read + '_>,
+        input: Option<&mut dyn std::io::Read>,
+        command: &mut impl Command,
+    ) -> Result<(), Error> {
+        let (mut src, in_compression) = if self.decompress && command.decompress_input() {
+            Compression::detect_reader(src)?
+        } else {
+            (src, None)
+        };
+        if let Some(member) = &self.member {
+            self.exec_member(&mut src, member, input, command, in_compression)
+        } else if self.hexdump {
+            let data = hexdump::to_bytes(&read_hexdump(self.blocksize, &mut src)?);
+            self.exec_impl(&mut data.as_slice(), input, command, in_compression)
+        } else {
+            self.exec_impl(&mut src, input, command, in_compression)
+        }
+    }
+
     pub fn exec(&self, command: &mut impl Command) -> Result<(), Error> {
+        if self.in_place && !command.edits_input() {
+            bail!("'in-place' can't be used with a subcommand which writes files");
+        }
         if let Some(in_file) = &self.in_file {
             let p = std::path::Path::new(&in_file);
             if !p.exists() {
                 bail!("{} does not exists!", in_file);
             }
-            let mut f = std::fs::File::open(p)?;
+            let f = Box::new(std::fs::File::open(p)?);
 
             if atty::isnt(atty::Stream::Stdin) {
-                self.exec_impl(&mut f, Some(&mut std::io::stdin()), command)
+                self.exec_source(f, Some(&mut std::io::stdin()), command)
             } else {
-                self.exec_impl(&mut f, None, command)
+                self.exec_source(f, None, command)
             }
         } else {
             if atty::isnt(atty::Stream::Stdin) {
-                self.exec_impl(&mut std::io::stdin(), None, command)
+                self.exec_source(Box::new(std::io::stdin()), None, command)
             } else {
                 bail!("Some source is required, either <FILE> or STDIN")
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::tar;
+    use crate::cli;
+    use crate::elf;
+    use crate::position::PositionParser;
+
+    /// Parses the command line `args` of bytie.
+    fn matches(args: &[&str]) -> ArgMatches<'static> {
+        let args: Vec<std::ffi::OsString> = args.iter().map(|a| a.into()).collect();
+        cli::get_matches_from(&args)
+    }
+
+    /// Runs the command line `args` with the subcommand created by `f`.
+    fn exec<C, F>(args: &[&str], f: F) -> Result<(), Error>
+    where
+        C: Command,
+        F: FnOnce(&ArgMatches, &PositionParser) -> Result<C, Error>,
+    {
+        let m = matches(args);
+        let runner = CommandRunner::from_matches(&m)?;
+        let parser = PositionParser::new(|| runner.read_input());
+        runner.exec(&mut f(m.subcommand().1.unwrap(), &parser)?)
+    }
+
+    #[test]
+    fn test_member() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("in.tar").to_str().unwrap().to_string();
+        let out = dir.path().join("out").to_str().unwrap().to_string();
+        std::fs::write(&file, tar::tests::archive()).unwrap();
+
+        let args = [
+            "bytie",
+            "--member",
+            "etc/hosts",
+            "-o",
+            &out,
+            &file,
+            "delete",
+            "0+4",
+        ];
+        assert!(exec(&args, delete::DeleteCommand::from_matches).is_ok());
+        let archive = Archive::parse(std::fs::read(&out).unwrap()).unwrap();
+        assert_eq!(archive.member("etc/hosts").unwrap(), b"0.0.1 localhost\n");
+        assert_eq!(archive.member("etc/pax.txt").unwrap(), b"12345");
+
+        let member = format!("{}!etc/hosts", file);
+        let args = ["bytie", "--extract", "-o", &out, &member, "delete", "0+4"];
+        assert!(exec(&args, delete::DeleteCommand::from_matches).is_ok());
+        assert_eq!(std::fs::read(&out).unwrap(), b"0.0.1 localhost\n");
+
+        let args = ["bytie", "--extract", &file, "delete", "0+4"];
+        assert!(exec(&args, delete::DeleteCommand::from_matches).is_err());
+    }
+
+    #[test]
+    fn test_decompress() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let write_gz = |name: &str, data: &[u8]| {
+            let mut f = std::fs::File::create(path(name)).unwrap();
+            write_compressed(&mut f, Some(Compression::Gzip), |out| {
+                Ok(out.write_all(data)?)
+            })
+            .unwrap();
+        };
+        let (a, a_gz, b_gz, c_gz) = (path("a"), path("a.gz"), path("b.gz"), path("c.gz"));
+        let (out, patch) = (path("out"), path("patch"));
+        std::fs::write(&a, "Hallo Welt, Hallo Welt").unwrap();
+        write_gz("a.gz", b"Hallo Welt, Hallo Welt");
+        write_gz("b.gz", b"Hallo Welt, Hallo Welt");
+        write_gz("c.gz", b"Hallo Welt, Hallo World");
+        let read = || std::fs::read(&out).unwrap();
+
+        // inflate works on the compressed input itself
+        let args = ["bytie", "-o", &out, &a_gz, "inflate", "0"];
+        assert!(exec(&args, inflate::InflateCommand::from_matches).is_ok());
+        assert_eq!(read(), b"Hallo Welt, Hallo Welt".to_vec());
+
+        // plain files which merely begin like a compressed one
+        let plain = path("plain");
+        for data in &[&b"BZhello world"[..], &b"\x1f\x8b no gzip"[..]] {
+            std::fs::write(&plain, data).unwrap();
+            let args = ["bytie", "-o", &out, &plain, "cut", "0+5"];
+            assert!(exec(&args, cut::CutCommand::from_matches).is_ok());
+            assert_eq!(read(), data[..5].to_vec());
+        }
+
+        let args = ["bytie", "-o", &out, &a_gz, "diff", &b_gz];
+        assert!(exec(&args, |m, _| diff::DiffCommand::from_matches(m)).is_ok());
+        assert!(read().is_empty());
+        let args = ["bytie", "-o", &out, "--no-decompress", &a, "diff", &b_gz];
+        assert!(exec(&args, |m, _| diff::DiffCommand::from_matches(m)).is_ok());
+        assert!(!read().is_empty());
+
+        let args = ["bytie", "-o", &out, &a_gz, "delta", &b_gz];
+        assert!(exec(&args, |m, _| delta::DeltaCommand::from_matches(m)).is_ok());
+        let script = read();
+        let args = ["bytie", "-o", &out, &a, "delta", &a];
+        assert!(exec(&args, |m, _| delta::DeltaCommand::from_matches(m)).is_ok());
+        assert_eq!(read(), script);
+
+        let args = ["bytie", "-o", &out, &a, "merge", &b_gz, &c_gz];
+        assert!(exec(&args, |m, _| merge::MergeCommand::from_matches(m)).is_ok());
+        assert_eq!(read(), b"Hallo Welt, Hallo World".to_vec());
+
+        let args = [
+            "bytie", "-o", &patch, &a_gz, "patch", "create", &c_gz, "-f", "ips",
+        ];
+        assert!(exec(&args, |m, _| patch::PatchCommand::from_matches(m)).is_ok());
+        let args = ["bytie", "-o", &out, &a, "patch", "apply", &patch];
+        assert!(exec(&args, |m, _| patch::PatchCommand::from_matches(m)).is_ok());
+        assert_eq!(read(), b"Hallo Welt, Hallo World".to_vec());
+
+        let part = format!("{}@0+5", b_gz);
+        let args = ["bytie", "-o", &out, &a, "join", &part];
+        assert!(exec(&args, |m, _| join::JoinCommand::from_matches(m)).is_ok());
+        assert_eq!(read(), b"Hallo Welt, Hallo WeltHallo".to_vec());
+
+        let args = [
+            "bytie", "-o", &out, &a, "overlay", &c_gz, "--at", "6", "--range", "18+5",
+        ];
+        assert!(exec(&args, overlay::OverlayCommand::from_matches).is_ok());
+        assert_eq!(read(), b"Hallo World Hallo Welt".to_vec());
+    }
+
+    #[test]
+    fn test_elf() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let (fw, a, version, out) = (path("fw.elf"), path("a"), path("v"), path("out"));
+        std::fs::write(&fw, elf::tests::elf()).unwrap();
+        std::fs::write(&a, "Hallo").unwrap();
+        std::fs::write(&version, "2.0").unwrap();
+        let read = |name: &str| std::fs::read(name).unwrap();
+
+        let target = format!("elf:.text={}", path("text"));
+        let args = ["bytie", "-o", &out, &fw, "cut", &target];
+        assert!(exec(&args, cut::CutCommand::from_matches).is_ok());
+        assert_eq!(read(&path("text")), b"TEXTTEXTTEXTTEXT".to_vec());
+
+        // the position of a part refers to the part
+        let part = format!("{}@elf:.rodata", fw);
+        let args = ["bytie", "-o", &out, &a, "join", &part];
+        assert!(exec(&args, |m, _| join::JoinCommand::from_matches(m)).is_ok());
+        assert_eq!(read(&out), b"Halloversion 1.2.3\0\0\0".to_vec());
+
+        let args = [
+            "bytie",
+            "-o",
+            &out,
+            &fw,
+            "overlay",
+            &version,
+            "--at",
+            "elf:sym:version_string+8",
+        ];
+        assert!(exec(&args, overlay::OverlayCommand::from_matches).is_ok());
+        assert_eq!(read(&out)[0x70..0x80], b"version 2.0.3\0\0\0"[..]);
+
+        // only positions are resolved
+        let args = [
+            "bytie",
+            "-o",
+            &out,
+            &fw,
+            "cut",
+            "elf:sym:main",
+            "--format",
+            "c",
+            "--name",
+            "elf:x",
+        ];
+        assert!(exec(&args, cut::CutCommand::from_matches).is_ok());
+        assert!(String::from_utf8(read(&out))
+            .unwrap()
+            .starts_with("const uint8_t elf:x[] = {"));
+    }
+
+    #[test]
+    fn test_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("in").to_str().unwrap().to_string();
+        let prefix = dir.path().join("part_").to_str().unwrap().to_string();
+        std::fs::write(&file, "foobar").unwrap();
+
+        let args = [
+            "bytie", "-i", &file, "split", "--size", "3", "--prefix", &prefix,
+        ];
+        assert!(exec(&args, |m, _| split::SplitCommand::from_matches(m)).is_err());
+        assert_eq!(std::fs::read(&file).unwrap(), b"foobar".to_vec());
+        assert!(!dir.path().join("part_000").exists());
+
+        let target = format!("0+3={}", prefix);
+        let args = ["bytie", "-i", &file, "cut", &target];
+        assert!(exec(&args, cut::CutCommand::from_matches).is_err());
+        assert_eq!(std::fs::read(&file).unwrap(), b"foobar".to_vec());
+        assert!(!dir.path().join("part_").exists());
+
+        let args = ["bytie", "-i", &file, "cut", "0+3"];
+        assert!(exec(&args, cut::CutCommand::from_matches).is_ok());
+        assert_eq!(std::fs::read(&file).unwrap(), b"foo".to_vec());
+    }
+}
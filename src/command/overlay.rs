@@ -0,0 +1,147 @@
+use crate::command::{copy, open, read_all, spool, unspool};
+use crate::defs;
+use crate::position::{parse_number, Position, PositionParser};
+use clap::{value_t, ArgMatches};
+use failure::{bail, Error};
+use std::io::Write;
+
+pub struct OverlayCommand {
+    file: String,
+    at: usize,
+    range: Option<Position>,
+    grow: bool,
+    decompress: bool,
+}
+
+impl OverlayCommand {
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        let file = value_t!(m, "overlay", String)?;
+        let at = parse_number(&parser.offset(&value_t!(m, "at", String)?)?)?;
+        let grow = m.is_present("grow");
+        let decompress = !m.is_present(defs::NO_DECOMPRESS_P);
+        // ELF references of the range refer to the overlay file
+        let range = match value_t!(m, "range", String) {
+            Ok(range) => Some(
+                PositionParser::new(|| read_all(defs::BLOCKSIZE, &mut open(&file, decompress)?))
+                    .position(&range)?,
+            ),
+            Err(_) => None,
+        };
+        Ok(Self {
+            file,
+            at,
+            range,
+            grow,
+            decompress,
+        })
+    }
+
+    /// Reads the overlay data, which is the selected range of the file.
+    fn read_overlay(&self, blocksize: usize) -> Result<Vec<u8>, Error> {
+        let mut f = open(&self.file, self.decompress)?;
+        let mut data = vec![];
+        match &self.range {
+            Some(range) => {
+                let skipped = copy(blocksize, &mut f, &mut std::io::sink(), Some(range.begin))?;
+                let count = range.end.map(|end| end - range.begin + 1);
+                let n = copy(blocksize, &mut f, &mut data, count)?;
+                if skipped < range.begin || n < count.unwrap_or(0) {
+                    bail!("--range exceeds the end of {}", self.file)
+                }
+            }
+            None => {
+                copy(blocksize, &mut f, &mut data, None)?;
+            }
+        }
+        Ok(data)
+    }
+}
+
+impl crate::command::Command for OverlayCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        let data = self.read_overlay(blocksize)?;
+
+        // nothing is written before the input is known to be long enough
+        let mut head = spool();
+        let n = copy(blocksize, source, &mut head, Some(self.at))?;
+        if n < self.at {
+            if !self.grow {
+                bail!(
+                    "--at {} lies beyond the end of the input, use --grow",
+                    self.at
+                )
+            }
+            head.write_all(&vec![0; self.at - n])?;
+        }
+        let n = copy(blocksize, source, &mut std::io::sink(), Some(data.len()))?;
+        if n < data.len() && !self.grow {
+            bail!(
+                "The overlay of {} bytes exceeds the end of the input, use --grow",
+                data.len()
+            )
+        }
+        unspool(blocksize, head, out)?;
+        out.write_all(&data)?;
+        copy(blocksize, source, out, None)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn test_overlay() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("part").to_str().unwrap().to_string();
+        std::fs::write(&file, "Welt").unwrap();
+
+        let mut cmd = OverlayCommand {
+            file,
+            at: 2,
+            range: None,
+            grow: false,
+            decompress: true,
+        };
+        let input = "Hallo_____";
+        for bs in vec![1, 2, 3, 4, 10] {
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(bs, &mut input.as_bytes(), &mut out, None).is_ok());
+            assert_eq!(out, b"HaWelt____".to_vec());
+        }
+
+        cmd.range = Some("1+2".parse().unwrap());
+        cmd.at = 9;
+        for bs in vec![1, 2, 3, 4, 10] {
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(bs, &mut input.as_bytes(), &mut out, None).is_err());
+            assert!(out.is_empty());
+        }
+        cmd.grow = true;
+        let mut out: Vec<u8> = vec![];
+        assert!(cmd.run(3, &mut input.as_bytes(), &mut out, None).is_ok());
+        assert_eq!(out, b"Hallo____el".to_vec());
+
+        cmd.at = 12;
+        let mut out: Vec<u8> = vec![];
+        assert!(cmd.run(3, &mut input.as_bytes(), &mut out, None).is_ok());
+        assert_eq!(out, b"Hallo_____\0\0el".to_vec());
+        cmd.grow = false;
+        let mut out: Vec<u8> = vec![];
+        assert!(cmd.run(3, &mut input.as_bytes(), &mut out, None).is_err());
+        assert!(out.is_empty());
+
+        cmd.range = Some("2+3".parse().unwrap());
+        cmd.at = 0;
+        assert!(cmd.run(3, &mut input.as_bytes(), &mut out, None).is_err());
+    }
+}
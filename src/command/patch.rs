@@ -0,0 +1,82 @@
+use crate::command::{copy, open, read_all, spool};
+use crate::defs;
+use crate::patch::{bsdiff, Format};
+use clap::{value_t, ArgMatches};
+use failure::{bail, Error};
+
+enum Action {
+    Apply,
+    Create,
+}
+
+pub struct PatchCommand {
+    action: Action,
+    format: Option<Format>,
+    file: String,
+    decompress: bool,
+}
+
+impl PatchCommand {
+    pub fn from_matches(m: &ArgMatches) -> Result<Self, Error> {
+        let decompress = !m.is_present(defs::NO_DECOMPRESS_P);
+        let (action, m) = match m.subcommand() {
+            ("apply", Some(m)) => (Action::Apply, m),
+            ("create", Some(m)) => (Action::Create, m),
+            _ => bail!("Either 'apply' or 'create' is required"),
+        };
+        let file = value_t!(m, "file", String)?;
+        let format = match value_t!(m, "format", String) {
+            Ok(format) => Some(format.parse::<Format>()?),
+            Err(_) => None,
+        };
+        if let Action::Create = action {
+            if format.is_none() {
+                bail!("Creating a patch requires a <FORMAT>")
+            }
+        }
+
+        Ok(Self {
+            action,
+            format,
+            file,
+            decompress,
+        })
+    }
+}
+
+impl crate::command::Command for PatchCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        let other = read_all(blocksize, &mut open(&self.file, self.decompress)?)?;
+
+        let data = match self.action {
+            Action::Apply => {
+                let format = match self.format {
+                    Some(format) => format,
+                    None => Format::detect(&other)?,
+                };
+                if format == Format::Bsdiff {
+                    // bsdiff seeks in the source, which is streamed into a spool
+                    let mut source_spool = spool();
+                    copy(blocksize, source, &mut source_spool, None)?;
+                    bsdiff::apply_from(blocksize, &mut source_spool, &other, out)?;
+                    out.flush()?;
+                    return Ok(());
+                }
+                format.apply(&read_all(blocksize, source)?, &other)?
+            }
+            Action::Create => self
+                .format
+                .unwrap()
+                .create(&read_all(blocksize, source)?, &other)?,
+        };
+        out.write_all(&data)?;
+        out.flush()?;
+        Ok(())
+    }
+}
@@ -1,22 +1,57 @@
+use crate::command::{copy, read_hexdump_value};
+use crate::hexdump::Chunk;
+use crate::position::{parse_number, PositionParser};
 use clap::{value_t, ArgMatches};
 use failure::{bail, Error};
-use humanize_rs::bytes::Bytes;
 
 pub struct ReplaceCommand {
     begin: usize,
     value: Option<Vec<u8>>,
+    dump: bool,
 }
 impl ReplaceCommand {
-    pub fn from_matches(m: &ArgMatches) -> Result<Self, Error> {
-        let begin = value_t!(m, "begin", String)?;
-        let begin = begin.parse::<Bytes>()?.size();
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        let begin = parser.offset(&value_t!(m, "begin", String)?)?;
+        let begin = parse_number(&begin)?;
         let value = if let Ok(value) = value_t!(m, "value", String) {
             Some(value.as_bytes().to_vec())
         } else {
             None
         };
+        let dump = m.is_present("dump");
 
-        Ok(Self { begin, value })
+        Ok(Self { begin, value, dump })
+    }
+
+    /// Writes every chunk of a dump at `begin` plus the chunk offset, the
+    /// source is zero-padded if a chunk lies behind its end.
+    fn replace_chunks(
+        &self,
+        chunks: &[Chunk],
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+    ) -> Result<(), Error> {
+        let mut pos = 0;
+        for chunk in chunks {
+            let at = self.begin + chunk.offset;
+            let n = copy(blocksize, source, out, Some(at - pos))?;
+            if n < at - pos {
+                out.write_all(&vec![0; at - pos - n])?;
+            }
+            out.write_all(&chunk.data)?;
+            copy(
+                blocksize,
+                source,
+                &mut std::io::sink(),
+                Some(chunk.data.len()),
+            )?;
+            pos = at + chunk.data.len();
+        }
+        copy(blocksize, source, out, None)?;
+
+        out.flush()?;
+        Ok(())
     }
 }
 
@@ -32,6 +67,11 @@ impl crate::command::Command for ReplaceCommand {
             bail!("Well, as no <VALUE> input parameter has been provided, some input should be provided by STDIN.")
         }
 
+        if self.dump {
+            let chunks = read_hexdump_value(blocksize, self.value.as_ref(), input)?;
+            return self.replace_chunks(&chunks, blocksize, source, out);
+        }
+
         let mut buffer = vec![0; blocksize];
         let mut in_total_read = 0;
         let mut offset = 0;
@@ -127,6 +167,7 @@ mod tests {
         let mut cmd = ReplaceCommand {
             begin: 0,
             value: None,
+            dump: false,
         };
         let mut out: Vec<u8> = vec![];
 
@@ -164,6 +205,7 @@ mod tests {
         let mut cmd = ReplaceCommand {
             begin: 0,
             value: None,
+            dump: false,
         };
         let mut out: Vec<u8> = vec![];
 
@@ -204,4 +246,41 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_dump() {
+        let mut cmd = ReplaceCommand {
+            begin: 2,
+            value: Some(b"00000000: 4142  AB\n00000004: 43  C\n".to_vec()),
+            dump: true,
+        };
+        let input = b"0123456789".to_vec();
+
+        for bs in vec![1, 2, 3, 4, 10] {
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(bs, &mut input.as_slice(), &mut out, None).is_ok());
+            assert_eq!(b"01AB45C789".to_vec(), out);
+        }
+
+        // data behind the end of the source is zero-padded
+        cmd.begin = 9;
+        let mut out: Vec<u8> = vec![];
+        assert!(cmd.run(4, &mut input.as_slice(), &mut out, None).is_ok());
+        assert_eq!(b"012345678AB\0\0C".to_vec(), out);
+    }
+
+    #[test]
+    fn test_begin() {
+        let parser = PositionParser::new(|| bail!("no ELF"));
+        for (begin, exp) in vec![("0x10", 16), ("0X1f", 31), ("1kb", 1000)] {
+            let args: Vec<std::ffi::OsString> = vec!["bytie", "replace", begin, "--value", "x"]
+                .into_iter()
+                .map(|a| a.into())
+                .collect();
+            let m = crate::cli::get_matches_from(&args);
+            let cmd =
+                ReplaceCommand::from_matches(m.subcommand_matches("replace").unwrap(), &parser);
+            assert_eq!(cmd.unwrap().begin, exp);
+        }
+    }
 }
@@ -0,0 +1,305 @@
+use crate::command::{copy, spool};
+use crate::position::parse_number;
+use clap::{value_t, ArgMatches};
+use failure::{bail, Error};
+use regex::bytes::{Regex, RegexBuilder};
+use std::io::{Seek, SeekFrom, Write};
+
+/// Maximum length of a match of the `--at` pattern, the bytes which might
+/// contain the beginning of a match are held back while streaming.
+static MAX_MATCH: usize = 64 * 1024;
+
+enum Mode {
+    /// Parts of the given size, the last part might be smaller.
+    Size(usize),
+    /// The given number of parts of (nearly) equal size.
+    Chunks(usize),
+    /// A new part begins at each match of the pattern.
+    At(Regex),
+}
+
+pub struct SplitCommand {
+    mode: Mode,
+    prefix: String,
+}
+
+/// Compiles a pattern like `/\xff\xd8/` (slashes are optional) which matches
+/// bytes instead of unicode characters.
+fn parse_pattern(pattern: &str) -> Result<Regex, Error> {
+    let pattern = if pattern.len() >= 2 && pattern.starts_with('/') && pattern.ends_with('/') {
+        &pattern[1..pattern.len() - 1]
+    } else {
+        pattern
+    };
+    Ok(RegexBuilder::new(pattern).unicode(false).build()?)
+}
+
+impl SplitCommand {
+    pub fn from_matches(m: &ArgMatches) -> Result<Self, Error> {
+        let mode = if let Ok(size) = value_t!(m, "size", String) {
+            Mode::Size(parse_number(&size)?)
+        } else if let Ok(chunks) = value_t!(m, "chunks", String) {
+            Mode::Chunks(parse_number(&chunks)?)
+        } else {
+            Mode::At(parse_pattern(&value_t!(m, "at", String)?)?)
+        };
+        match mode {
+            Mode::Size(0) => bail!("<size> has to be >= 1"),
+            Mode::Chunks(0) => bail!("<chunks> has to be >= 1"),
+            _ => (),
+        }
+        let prefix = value_t!(m, "prefix", String).unwrap_or_else(|_| "part_".to_string());
+        Ok(Self { mode, prefix })
+    }
+
+    fn create(&self, index: usize) -> Result<(String, std::fs::File), Error> {
+        let name = format!("{}{:03}", self.prefix, index);
+        let f = std::fs::File::create(&name)?;
+        Ok((name, f))
+    }
+
+    /// Writes the parts of `source` and returns the file names.
+    fn split(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+    ) -> Result<Vec<String>, Error> {
+        let mut names = vec![];
+        match &self.mode {
+            Mode::Size(size) => loop {
+                let (name, mut f) = self.create(names.len())?;
+                if copy(blocksize, source, &mut f, Some(*size))? == 0 {
+                    std::fs::remove_file(&name)?;
+                    break;
+                }
+                names.push(name);
+            },
+            Mode::Chunks(chunks) => {
+                // the length is counted in a first pass over a spooled copy
+                let mut data = spool();
+                let len = copy(blocksize, source, &mut data, None)?;
+                data.seek(SeekFrom::Start(0))?;
+                for i in 0..*chunks {
+                    let (name, mut f) = self.create(i)?;
+                    copy(
+                        blocksize,
+                        &mut data,
+                        &mut f,
+                        Some(len / chunks + (i < len % chunks) as usize),
+                    )?;
+                    names.push(name);
+                }
+            }
+            Mode::At(re) => names = self.split_at(blocksize, source, re)?,
+        }
+        Ok(names)
+    }
+
+    /// Appends `data` to the current part, which is created if necessary.
+    fn append(
+        &self,
+        part: &mut Option<std::fs::File>,
+        names: &mut Vec<String>,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        if part.is_none() {
+            let (name, f) = self.create(names.len())?;
+            names.push(name);
+            *part = Some(f);
+        }
+        part.as_mut().unwrap().write_all(data)?;
+        Ok(())
+    }
+
+    /// Streams `source` into parts which begin at the matches of `re`, which
+    /// must not be longer than `MAX_MATCH`.
+    fn split_at(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        re: &Regex,
+    ) -> Result<Vec<String>, Error> {
+        let mut names = vec![];
+        let mut part = None;
+        // bytes which are not written yet, their offset in the input and the
+        // position in them where the search continues
+        let mut window = vec![];
+        let mut offset = 0;
+        let mut pos = 0;
+        loop {
+            // the held back bytes are searched again, hence larger steps
+            let eof = copy(blocksize, source, &mut window, Some(MAX_MATCH))? < MAX_MATCH;
+            let mut keep = if eof {
+                window.len()
+            } else {
+                window.len().saturating_sub(MAX_MATCH)
+            };
+            while pos <= window.len() {
+                let m = match re.find_at(&window, pos) {
+                    Some(m) => m,
+                    None => break,
+                };
+                if !eof && m.end() == window.len() {
+                    // the match might continue in the next block
+                    keep = std::cmp::min(keep, m.start());
+                    break;
+                }
+                let (start, end) = (m.start(), m.end());
+                pos = end;
+                if offset + start > 0 {
+                    self.append(&mut part, &mut names, &window[..start])?;
+                    part = None;
+                    window.drain(..start);
+                    offset += start;
+                    keep = keep.saturating_sub(start);
+                    pos -= start;
+                }
+                if start == end {
+                    pos += 1;
+                }
+            }
+            self.append(&mut part, &mut names, &window[..keep])?;
+            window.drain(..keep);
+            offset += keep;
+            pos = pos.saturating_sub(keep);
+            if eof {
+                break;
+            }
+        }
+        Ok(names)
+    }
+}
+
+impl crate::command::Command for SplitCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        for name in self.split(blocksize, source)? {
+            writeln!(out, "{}", name)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    fn edits_input(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    fn run_split(mode: Mode, input: &[u8], bs: usize) -> Vec<Vec<u8>> {
+        let dir = tempfile::tempdir().unwrap();
+        let prefix = dir.path().join("part_").to_str().unwrap().to_string();
+        let cmd = SplitCommand { mode, prefix };
+        let mut out: Vec<u8> = vec![];
+        assert!(cmd.run(bs, &mut &input[..], &mut out, None).is_ok());
+        std::str::from_utf8(&out)
+            .unwrap()
+            .lines()
+            .enumerate()
+            .map(|(i, name)| {
+                assert_eq!(name, format!("{}{:03}", cmd.prefix, i));
+                std::fs::read(name).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_size() {
+        for bs in vec![1, 2, 3, 4, 10, 1024] {
+            let parts = run_split(Mode::Size(4), b"HalloWelt", bs);
+            assert_eq!(
+                parts,
+                vec![b"Hall".to_vec(), b"oWel".to_vec(), b"t".to_vec()]
+            );
+            let parts = run_split(Mode::Size(3), b"HalloWelt", bs);
+            assert_eq!(parts.len(), 3);
+            assert!(run_split(Mode::Size(3), b"", bs).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_chunks() {
+        for bs in vec![1, 2, 3, 4, 10] {
+            let parts = run_split(Mode::Chunks(4), b"HalloWelt", bs);
+            assert_eq!(
+                parts,
+                vec![
+                    b"Hal".to_vec(),
+                    b"lo".to_vec(),
+                    b"We".to_vec(),
+                    b"lt".to_vec()
+                ]
+            );
+            let parts = run_split(Mode::Chunks(3), b"ab", bs);
+            assert_eq!(parts, vec![b"a".to_vec(), b"b".to_vec(), b"".to_vec()]);
+        }
+    }
+
+    #[test]
+    fn test_at() {
+        let input = b"\xff\xd8one\xff\xd8two\xff\xd9\xff\xd8three";
+        for bs in vec![1, 2, 3, 4, 10, 1024] {
+            let parts = run_split(Mode::At(parse_pattern(r"/\xff\xd8/").unwrap()), input, bs);
+            assert_eq!(
+                parts,
+                vec![
+                    b"\xff\xd8one".to_vec(),
+                    b"\xff\xd8two\xff\xd9".to_vec(),
+                    b"\xff\xd8three".to_vec()
+                ]
+            );
+            let parts = run_split(Mode::At(parse_pattern("t").unwrap()), b"xtwotxt", bs);
+            assert_eq!(
+                parts,
+                vec![
+                    b"x".to_vec(),
+                    b"two".to_vec(),
+                    b"tx".to_vec(),
+                    b"t".to_vec()
+                ]
+            );
+        }
+        // matches which straddle blocks or might grow
+        for bs in vec![1, 2, 3, 4, 10, 1024] {
+            let parts = run_split(Mode::At(parse_pattern(r"\xff+\x00").unwrap()), input, bs);
+            assert_eq!(parts, vec![input.to_vec()]);
+            let input = b"ab\xff\xff\x00cd\xff\x00\xff";
+            let parts = run_split(Mode::At(parse_pattern(r"\xff+\x00").unwrap()), input, bs);
+            assert_eq!(
+                parts,
+                vec![
+                    b"ab".to_vec(),
+                    b"\xff\xff\x00cd".to_vec(),
+                    b"\xff\x00\xff".to_vec()
+                ]
+            );
+            let parts = run_split(Mode::At(parse_pattern("").unwrap()), b"abc", bs);
+            assert_eq!(parts, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+        }
+
+        // more input than is held back
+        let mut input = vec![b'_'; 3 * MAX_MATCH];
+        for at in vec![MAX_MATCH - 1, 2 * MAX_MATCH + 5] {
+            input[at..at + 3].copy_from_slice(b"\xff\xff\x00");
+        }
+        for bs in vec![1, 1000, 4096] {
+            let parts = run_split(Mode::At(parse_pattern(r"\xff+\x00").unwrap()), &input, bs);
+            let lens: Vec<usize> = parts.iter().map(|p| p.len()).collect();
+            assert_eq!(lens, vec![MAX_MATCH - 1, MAX_MATCH + 6, MAX_MATCH - 5]);
+            assert_eq!(parts.concat(), input);
+        }
+        assert!(parse_pattern("(").is_err());
+    }
+}
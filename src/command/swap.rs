@@ -0,0 +1,88 @@
+use crate::command::read_all;
+use crate::position::{Position, PositionParser};
+use clap::{value_t, ArgMatches};
+use failure::{bail, Error};
+
+pub struct SwapCommand {
+    first: Position,
+    second: Position,
+}
+
+impl SwapCommand {
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        let first = parser.position(&value_t!(m, "first", String)?)?;
+        let second = parser.position(&value_t!(m, "second", String)?)?;
+        Ok(Self { first, second })
+    }
+
+    /// Exchanges both ranges, the data between them is shifted if their
+    /// lengths differ.
+    fn swap(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut a = self.first.range(data.len())?;
+        let mut b = self.second.range(data.len())?;
+        if b.start < a.start {
+            std::mem::swap(&mut a, &mut b);
+        }
+        if a.end > b.start {
+            bail!("The ranges {} and {} overlap", self.first, self.second)
+        }
+        let mut swapped = Vec::with_capacity(data.len());
+        swapped.extend_from_slice(&data[..a.start]);
+        swapped.extend_from_slice(&data[b.clone()]);
+        swapped.extend_from_slice(&data[a.end..b.start]);
+        swapped.extend_from_slice(&data[a]);
+        swapped.extend_from_slice(&data[b.end..]);
+        Ok(swapped)
+    }
+}
+
+impl crate::command::Command for SwapCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        let data = read_all(blocksize, source)?;
+        out.write_all(&self.swap(&data)?)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn test_swap() {
+        let input = "Hallo, Welt!";
+        for bs in vec![1, 2, 3, 4, 10] {
+            for (first, second, exp) in vec![
+                ("0+5", "7+4", "Welt, Hallo!"),
+                ("7+4", "0+5", "Welt, Hallo!"),
+                ("0+5", "5+2", ", HalloWelt!"),
+                ("11", "0+1", "!allo, WeltH"),
+            ] {
+                let cmd = SwapCommand {
+                    first: first.parse().unwrap(),
+                    second: second.parse().unwrap(),
+                };
+                let mut out: Vec<u8> = vec![];
+                assert!(cmd.run(bs, &mut input.as_bytes(), &mut out, None).is_ok());
+                assert_eq!(exp, std::str::from_utf8(&out).unwrap());
+            }
+        }
+
+        for (first, second) in vec![("0+5", "4+2"), ("0+5", "10+3"), ("2", "0+3")] {
+            let cmd = SwapCommand {
+                first: first.parse().unwrap(),
+                second: second.parse().unwrap(),
+            };
+            let mut out: Vec<u8> = vec![];
+            assert!(cmd.run(4, &mut input.as_bytes(), &mut out, None).is_err());
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::command::{map_range, replace_range};
+use crate::position::{Position, PositionParser};
+use clap::{value_t, ArgMatches};
+use failure::{bail, Error};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Transform {
+    /// Reverses the order of all bytes of the range.
+    Reverse,
+    /// Reverses the byte order of each word of the given size.
+    Swap(usize),
+    /// Swaps the high and low nibble of each byte.
+    Nibble,
+    /// Reverses the bit order within each byte.
+    BitReverse,
+}
+
+impl Transform {
+    pub(crate) fn variants() -> &'static [&'static str] {
+        &["reverse", "swap16", "swap32", "swap64", "nibble", "bitrev"]
+    }
+
+    /// Applies the transformation to `data`, which is a multiple of the word
+    /// size.
+    fn apply(self, data: &mut [u8]) {
+        match self {
+            Transform::Reverse => data.reverse(),
+            Transform::Swap(size) => data.chunks_mut(size).for_each(|word| word.reverse()),
+            Transform::Nibble => data.iter_mut().for_each(|b| *b = b.rotate_left(4)),
+            Transform::BitReverse => data.iter_mut().for_each(|b| *b = b.reverse_bits()),
+        }
+    }
+}
+
+impl FromStr for Transform {
+    type Err = Error;
+
+    fn from_str(transform: &str) -> Result<Self, Error> {
+        Ok(match transform {
+            "reverse" => Transform::Reverse,
+            "swap16" => Transform::Swap(2),
+            "swap32" => Transform::Swap(4),
+            "swap64" => Transform::Swap(8),
+            "nibble" => Transform::Nibble,
+            "bitrev" => Transform::BitReverse,
+            _ => bail!("Unknown transformation '{}'", transform),
+        })
+    }
+}
+
+pub struct TransformCommand {
+    transform: Transform,
+    position: Position,
+}
+
+impl TransformCommand {
+    pub fn from_matches(m: &ArgMatches, parser: &PositionParser) -> Result<Self, Error> {
+        let transform = value_t!(m, "transform", String)?.parse::<Transform>()?;
+        let position = match value_t!(m, "position", String) {
+            Ok(position) => parser.position(&position)?,
+            Err(_) => Position {
+                begin: 0,
+                end: None,
+            },
+        };
+        Ok(Self {
+            transform,
+            position,
+        })
+    }
+}
+
+impl crate::command::Command for TransformCommand {
+    fn run(
+        &self,
+        blocksize: usize,
+        source: &mut dyn std::io::Read,
+        out: &mut dyn std::io::Write,
+        _input: Option<&mut dyn std::io::Read>,
+    ) -> Result<(), Error> {
+        let transform = self.transform;
+        match transform {
+            Transform::Reverse => {
+                // the whole range is needed, so it is buffered
+                replace_range(blocksize, source, out, &self.position, &mut |mut data| {
+                    transform.apply(&mut data);
+                    Ok(data)
+                })?
+            }
+            _ => {
+                let unit = match transform {
+                    Transform::Swap(size) => size,
+                    _ => 1,
+                };
+                map_range(blocksize, source, out, &self.position, unit, &mut |data| {
+                    transform.apply(data)
+                })?
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn test_transform() {
+        let input = b"__\x01\x02\x03\x04\x05\x06\x07\x08__";
+        for bs in vec![1, 2, 3, 4, 5, 10, 1024] {
+            for (transform, position, exp) in vec![
+                (
+                    "reverse",
+                    "2+8",
+                    &b"__\x08\x07\x06\x05\x04\x03\x02\x01__"[..],
+                ),
+                ("reverse", "0", b"__\x08\x07\x06\x05\x04\x03\x02\x01__"),
+                ("swap16", "2+8", b"__\x02\x01\x04\x03\x06\x05\x08\x07__"),
+                ("swap32", "2+8", b"__\x04\x03\x02\x01\x08\x07\x06\x05__"),
+                ("swap64", "2+8", b"__\x08\x07\x06\x05\x04\x03\x02\x01__"),
+                ("swap16", "0", b"__\x02\x01\x04\x03\x06\x05\x08\x07__"),
+                ("nibble", "2+2", b"__\x10\x20\x03\x04\x05\x06\x07\x08__"),
+                ("bitrev", "9", b"__\x01\x02\x03\x04\x05\x06\x07\x10\xfa\xfa"),
+            ] {
+                let cmd = TransformCommand {
+                    transform: transform.parse().unwrap(),
+                    position: position.parse().unwrap(),
+                };
+                let mut out: Vec<u8> = vec![];
+                assert!(cmd.run(bs, &mut &input[..], &mut out, None).is_ok());
+                assert_eq!(out, exp.to_vec());
+            }
+
+            for (transform, position) in vec![
+                ("swap16", "2+3"),
+                ("swap64", "0"),
+                ("swap16", "3"),
+                ("reverse", "13"),
+                ("reverse", "10+3"),
+                ("nibble", "10+3"),
+            ] {
+                let cmd = TransformCommand {
+                    transform: transform.parse().unwrap(),
+                    position: position.parse().unwrap(),
+                };
+                let mut out: Vec<u8> = vec![];
+                assert!(cmd.run(bs, &mut &input[..], &mut out, None).is_err());
+                assert!(out.is_empty());
+            }
+        }
+        assert!("swap24".parse::<Transform>().is_err());
+    }
+}
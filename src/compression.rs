@@ -0,0 +1,260 @@
+use failure::{bail, Error};
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// Compression of input and output files.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Compression {
+    Gzip,
+    Xz,
+    Zstd,
+    Bzip2,
+}
+
+impl FromStr for Compression {
+    type Err = Error;
+
+    fn from_str(compression: &str) -> Result<Compression, Error> {
+        Ok(match compression {
+            "gzip" | "gz" => Compression::Gzip,
+            "xz" => Compression::Xz,
+            "zstd" | "zst" => Compression::Zstd,
+            "bzip2" | "bz2" => Compression::Bzip2,
+            x => bail!("Unknown compression: {}", x),
+        })
+    }
+}
+
+/// Number of bytes which are needed to detect a compression.
+static MAGIC_LEN: usize = 10;
+
+/// Number of decompressed bytes which are read to verify a detected
+/// compression.
+static PROBE_LEN: usize = 4096;
+
+/// The reader of a detected compression, the bytes read from it are recorded
+/// until the compression has been verified.
+struct Probe<'a> {
+    inner: Box<dyn Read + 'a>,
+    recorded: Option<Vec<u8>>,
+}
+
+/// Reads from a `Probe` which is shared with `detect_reader`.
+struct ProbeReader<'a>(Rc<RefCell<Probe<'a>>>);
+
+impl Read for ProbeReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut probe = self.0.borrow_mut();
+        let n = probe.inner.read(buf)?;
+        if let Some(recorded) = &mut probe.recorded {
+            recorded.extend_from_slice(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+impl Compression {
+    pub(crate) fn variants() -> &'static [&'static str] {
+        &["gzip", "xz", "zstd", "bzip2"]
+    }
+
+    /// Detects the compression by the header at the beginning of `data`:
+    /// the gzip magic with deflate method and no reserved flags, the xz and
+    /// zstd magic or the bzip2 magic with block size followed by the magic of
+    /// a block or of the end of the stream.
+    pub(crate) fn detect(data: &[u8]) -> Option<Compression> {
+        match data {
+            [0x1f, 0x8b, 0x08, flags, ..] if flags & 0xe0 == 0 => Some(Compression::Gzip),
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Compression::Xz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Compression::Zstd),
+            [b'B', b'Z', b'h', b'1'..=b'9', 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, ..]
+            | [b'B', b'Z', b'h', b'1'..=b'9', 0x17, 0x72, 0x45, 0x38, 0x50, 0x90, ..] => {
+                Some(Compression::Bzip2)
+            }
+            _ => None,
+        }
+    }
+
+    /// Derives the compression from the extension of `path`, e.g. `foo.gz`.
+    pub(crate) fn from_path(path: &str) -> Option<Compression> {
+        match std::path::Path::new(path).extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip),
+            "xz" => Some(Compression::Xz),
+            "zst" => Some(Compression::Zstd),
+            "bz2" => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Peeks at the beginning of `r` and returns a reader which decompresses
+    /// it, if a compression has been detected. The data is passed as is if
+    /// the decoder rejects it.
+    pub(crate) fn detect_reader<'a>(
+        mut r: Box<dyn Read + 'a>,
+    ) -> Result<(Box<dyn Read + 'a>, Option<Compression>), Error> {
+        let mut magic = vec![];
+        (&mut r).take(MAGIC_LEN as u64).read_to_end(&mut magic)?;
+        let r: Box<dyn Read + 'a> = Box::new(std::io::Cursor::new(magic.clone()).chain(r));
+        let compression = match Compression::detect(&magic) {
+            Some(compression) => compression,
+            None => return Ok((r, None)),
+        };
+
+        let probe = Rc::new(RefCell::new(Probe {
+            inner: r,
+            recorded: Some(vec![]),
+        }));
+        let mut decoder = compression.decoder(Box::new(ProbeReader(probe.clone())))?;
+        let mut decompressed = vec![];
+        match (&mut decoder)
+            .take(PROBE_LEN as u64)
+            .read_to_end(&mut decompressed)
+        {
+            Ok(_) => {
+                probe.borrow_mut().recorded = None;
+                let r = std::io::Cursor::new(decompressed).chain(decoder);
+                Ok((Box::new(r), Some(compression)))
+            }
+            Err(_) => {
+                drop(decoder);
+                let probe = match Rc::try_unwrap(probe) {
+                    Ok(probe) => probe.into_inner(),
+                    Err(_) => bail!("The {:?} decoder is still in use", compression),
+                };
+                let recorded = probe.recorded.unwrap_or_default();
+                Ok((
+                    Box::new(std::io::Cursor::new(recorded).chain(probe.inner)),
+                    None,
+                ))
+            }
+        }
+    }
+
+    pub(crate) fn decoder<'a>(self, r: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>, Error> {
+        Ok(match self {
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(r)),
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(r)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(r)?),
+            Compression::Bzip2 => Box::new(bzip2::read::BzDecoder::new(r)),
+        })
+    }
+
+    pub(crate) fn encoder<W: Write>(self, w: W) -> Result<Encoder<W>, Error> {
+        Ok(match self {
+            Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                w,
+                flate2::Compression::default(),
+            )),
+            Compression::Xz => Encoder::Xz(xz2::write::XzEncoder::new(w, 6)),
+            Compression::Zstd => Encoder::Zstd(zstd::stream::write::Encoder::new(w, 0)?),
+            Compression::Bzip2 => {
+                Encoder::Bzip2(bzip2::write::BzEncoder::new(w, bzip2::Compression::Default))
+            }
+        })
+    }
+}
+
+/// Compressing writer, `finish` has to be called to complete the stream.
+pub(crate) enum Encoder<W: Write> {
+    Gzip(flate2::write::GzEncoder<W>),
+    Xz(xz2::write::XzEncoder<W>),
+    Zstd(zstd::stream::write::Encoder<W>),
+    Bzip2(bzip2::write::BzEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    pub(crate) fn finish(self) -> Result<W, Error> {
+        Ok(match self {
+            Encoder::Gzip(e) => e.finish()?,
+            Encoder::Xz(e) => e.finish()?,
+            Encoder::Zstd(e) => e.finish()?,
+            Encoder::Bzip2(e) => e.finish()?,
+        })
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Encoder::Gzip(e) => e,
+            Encoder::Xz(e) => e,
+            Encoder::Zstd(e) => e,
+            Encoder::Bzip2(e) => e,
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Compression::detect(b"\x1f\x8b\x08\x00"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::detect(b"\xfd7zXZ\x00\x00"),
+            Some(Compression::Xz)
+        );
+        assert_eq!(
+            Compression::detect(b"\x28\xb5\x2f\xfd"),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::detect(b"BZh91AY&SY"), Some(Compression::Bzip2));
+        assert_eq!(Compression::detect(b"BZhello world"), None);
+        assert_eq!(Compression::detect(b"\x1f\x8bXYZ"), None);
+        assert_eq!(Compression::detect(b"\x1f"), None);
+        assert_eq!(Compression::detect(b"Hallo"), None);
+
+        assert_eq!(
+            Compression::from_path("a/b.bin.gz"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(Compression::from_path("b.zst"), Some(Compression::Zstd));
+        assert_eq!(Compression::from_path("b.bin"), None);
+        assert_eq!(Compression::from_path("gz"), None);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let data = b"Hallo Welt! ".repeat(100);
+        for name in Compression::variants() {
+            let compression = name.parse::<Compression>().unwrap();
+            let mut e = compression.encoder(vec![]).unwrap();
+            e.write_all(&data).unwrap();
+            let compressed = e.finish().unwrap();
+            assert_eq!(Compression::detect(&compressed), Some(compression));
+
+            let (mut r, detected) = Compression::detect_reader(Box::new(&compressed[..])).unwrap();
+            assert_eq!(detected, Some(compression));
+            let mut decompressed = vec![];
+            r.read_to_end(&mut decompressed).unwrap();
+            assert_eq!(decompressed, data);
+        }
+
+        // a header which the decoder rejects is passed as is
+        for data in vec![
+            b"Hal".to_vec(),
+            b"BZh91AY&SY no bzip2 block".to_vec(),
+            b"\x1f\x8b\x08\x00 no deflate stream".repeat(200),
+        ] {
+            let (mut r, detected) = Compression::detect_reader(Box::new(&data[..])).unwrap();
+            assert_eq!(detected, None);
+            let mut read = vec![];
+            r.read_to_end(&mut read).unwrap();
+            assert_eq!(read, data);
+        }
+    }
+}
@@ -2,3 +2,8 @@ pub static OUTPUT_P: &str = "output";
 pub static IN_PLACE_P: &str = "in-place";
 pub static BLOCKSIZE_P: &str = "blocksize";
 pub static BLOCKSIZE: usize = 1024;
+pub static HEXDUMP_P: &str = "hexdump";
+pub static COMPRESS_P: &str = "compress";
+pub static NO_DECOMPRESS_P: &str = "no-decompress";
+pub static MEMBER_P: &str = "member";
+pub static EXTRACT_P: &str = "extract";
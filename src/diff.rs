@@ -0,0 +1,309 @@
+use std::ops::Range;
+
+/// Changes separated by less equal bytes than this are merged into a single
+/// hunk, this keeps the regions readable for binary data where random bytes
+/// happen to match.
+static MERGE_GAP: usize = 4;
+
+/// Upper bound for the edit cost which is explored to find an optimal split.
+/// When exceeded, the furthest reaching path is taken which keeps the diff
+/// valid but not necessarily minimal.
+static MAX_COST: isize = 1024;
+
+/// A region which differs between the old and the new data. One of the
+/// ranges might be empty for pure insertions or deletions.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) struct Hunk {
+    pub(crate) old: Range<usize>,
+    pub(crate) new: Range<usize>,
+}
+
+impl Hunk {
+    pub(crate) fn op(&self) -> &'static str {
+        if self.old.is_empty() {
+            "insert"
+        } else if self.new.is_empty() {
+            "delete"
+        } else {
+            "replace"
+        }
+    }
+}
+
+/// Computes the differing regions between `old` and `new` (Myers' algorithm,
+/// linear space variant), insertions and deletions are detected as such.
+pub(crate) fn diff(old: &[u8], new: &[u8]) -> Vec<Hunk> {
+    let max_d = std::cmp::min((old.len() + new.len()) / 2 + 2, MAX_COST as usize + 1);
+    let mut vf = V::new(max_d);
+    let mut vb = V::new(max_d);
+    let mut hunks: Vec<Hunk> = vec![];
+    conquer(
+        old,
+        0..old.len(),
+        new,
+        0..new.len(),
+        &mut vf,
+        &mut vb,
+        &mut hunks,
+    );
+
+    let mut merged: Vec<Hunk> = vec![];
+    for h in hunks {
+        match merged.last_mut() {
+            Some(last) if h.old.start - last.old.end < MERGE_GAP => {
+                last.old.end = h.old.end;
+                last.new.end = h.new.end;
+            }
+            _ => merged.push(h),
+        }
+    }
+    merged
+}
+
+struct V {
+    offset: isize,
+    v: Vec<usize>,
+}
+
+impl V {
+    fn new(max_d: usize) -> Self {
+        V {
+            offset: max_d as isize,
+            v: vec![0; 2 * max_d + 1],
+        }
+    }
+}
+
+impl std::ops::Index<isize> for V {
+    type Output = usize;
+    fn index(&self, k: isize) -> &usize {
+        &self.v[(k + self.offset) as usize]
+    }
+}
+
+impl std::ops::IndexMut<isize> for V {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.v[(k + self.offset) as usize]
+    }
+}
+
+fn common_prefix(old: &[u8], new: &[u8]) -> usize {
+    old.iter().zip(new).take_while(|(a, b)| a == b).count()
+}
+
+fn common_suffix(old: &[u8], new: &[u8]) -> usize {
+    old.iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+fn push(hunks: &mut Vec<Hunk>, old: Range<usize>, new: Range<usize>) {
+    match hunks.last_mut() {
+        Some(last) if last.old.end == old.start && last.new.end == new.start => {
+            last.old.end = old.end;
+            last.new.end = new.end;
+        }
+        _ => hunks.push(Hunk { old, new }),
+    }
+}
+
+/// Returns a point which lies on an (almost) optimal edit path.
+fn find_split(old: &[u8], new: &[u8], vf: &mut V, vb: &mut V) -> Option<(usize, usize)> {
+    let n = old.len();
+    let m = new.len();
+    let delta = n as isize - m as isize;
+    let odd = delta & 1 == 1;
+    let d_max = ((n + m) / 2 + 2) as isize;
+
+    vf[1] = 0;
+    vb[1] = 0;
+
+    for d in 0..d_max {
+        if d > MAX_COST {
+            // give up, take the forward path which made the most progress
+            let (x, y) = (-d + 1..d)
+                .step_by(2)
+                .map(|k| (vf[k], (vf[k] as isize - k) as usize))
+                .filter(|&(x, y)| x <= n && y <= m)
+                .max_by_key(|&(x, y)| x + y)?;
+            if (x, y) == (0, 0) || (x, y) == (n, m) {
+                return None;
+            }
+            return Some((x, y));
+        }
+
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && vf[k - 1] < vf[k + 1]) {
+                vf[k + 1]
+            } else {
+                vf[k - 1] + 1
+            };
+            let y = (x as isize - k) as usize;
+            let (x0, y0) = (x, y);
+            if x < n && y < m {
+                x += common_prefix(&old[x..], &new[y..]);
+            }
+            vf[k] = x;
+            if odd && (k - delta).abs() < d && vf[k] + vb[-(k - delta)] >= n {
+                return Some((x0, y0));
+            }
+        }
+
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && vb[k - 1] < vb[k + 1]) {
+                vb[k + 1]
+            } else {
+                vb[k - 1] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            if x < n && y < m {
+                let advance = common_suffix(&old[..n - x], &new[..m - y]);
+                x += advance;
+                y += advance;
+            }
+            vb[k] = x;
+            if !odd && (k - delta).abs() <= d && vb[k] + vf[-(k - delta)] >= n {
+                return Some((n - x, m - y));
+            }
+        }
+    }
+    None
+}
+
+fn conquer(
+    old: &[u8],
+    mut old_range: Range<usize>,
+    new: &[u8],
+    mut new_range: Range<usize>,
+    vf: &mut V,
+    vb: &mut V,
+    hunks: &mut Vec<Hunk>,
+) {
+    let prefix = common_prefix(&old[old_range.clone()], &new[new_range.clone()]);
+    old_range.start += prefix;
+    new_range.start += prefix;
+    let suffix = common_suffix(&old[old_range.clone()], &new[new_range.clone()]);
+    old_range.end -= suffix;
+    new_range.end -= suffix;
+
+    if old_range.is_empty() && new_range.is_empty() {
+        return;
+    }
+    if old_range.is_empty() || new_range.is_empty() {
+        push(hunks, old_range, new_range);
+        return;
+    }
+
+    match find_split(&old[old_range.clone()], &new[new_range.clone()], vf, vb) {
+        Some((x, y)) => {
+            let (x, y) = (old_range.start + x, new_range.start + y);
+            conquer(
+                old,
+                old_range.start..x,
+                new,
+                new_range.start..y,
+                vf,
+                vb,
+                hunks,
+            );
+            conquer(old, x..old_range.end, new, y..new_range.end, vf, vb, hunks);
+        }
+        None => push(hunks, old_range, new_range),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{thread_rng, Rng};
+
+    /// Applies the hunks on `old` which needs to result in `new`.
+    fn apply(old: &[u8], new: &[u8], hunks: &[Hunk]) -> Vec<u8> {
+        let mut out = vec![];
+        let mut pos = 0;
+        for h in hunks {
+            out.extend_from_slice(&old[pos..h.old.start]);
+            out.extend_from_slice(&new[h.new.clone()]);
+            pos = h.old.end;
+        }
+        out.extend_from_slice(&old[pos..]);
+        out
+    }
+
+    #[test]
+    fn test_equal() {
+        assert!(diff(b"", b"").is_empty());
+        assert!(diff(b"foobar", b"foobar").is_empty());
+    }
+
+    #[test]
+    fn test_insert_delete() {
+        let hunks = diff(b"foobar", b"fooXYZbar");
+        assert_eq!(
+            hunks,
+            vec![Hunk {
+                old: 3..3,
+                new: 3..6
+            }]
+        );
+        assert_eq!(hunks[0].op(), "insert");
+
+        let hunks = diff(b"0123456789abcdef", b"01234589abcdef");
+        assert_eq!(
+            hunks,
+            vec![Hunk {
+                old: 6..8,
+                new: 6..6
+            }]
+        );
+        assert_eq!(hunks[0].op(), "delete");
+    }
+
+    #[test]
+    fn test_shifted_change() {
+        // an insertion at the beginning must not turn into a full replacement
+        let old = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit".to_vec();
+        let mut new = b"XX".to_vec();
+        new.extend_from_slice(&old);
+        new[30] = b'#';
+        let hunks = diff(&old, &new);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].op(), "insert");
+        assert_eq!(hunks[1].op(), "replace");
+        assert_eq!(hunks[1].old, 28..29);
+    }
+
+    #[test]
+    fn test_random() {
+        let mut rng = thread_rng();
+        for _ in 0..50 {
+            let len: usize = rng.gen_range(0, 2000);
+            let old: Vec<u8> = (0..len).map(|_| rng.gen_range(0, 4)).collect();
+            let mut new = old.clone();
+            for _ in 0..rng.gen_range(0, 20) {
+                let at = rng.gen_range(0, new.len() + 1);
+                match rng.gen_range(0, 3) {
+                    0 => new.insert(at, rng.gen()),
+                    1 if at < new.len() => {
+                        new.remove(at);
+                    }
+                    _ if at < new.len() => new[at] = rng.gen(),
+                    _ => (),
+                }
+            }
+            let hunks = diff(&old, &new);
+            assert_eq!(apply(&old, &new, &hunks), new);
+        }
+    }
+
+    #[test]
+    fn test_expensive() {
+        let mut rng = thread_rng();
+        let old: Vec<u8> = (0..20000).map(|_| rng.gen()).collect();
+        let new: Vec<u8> = (0..20000).map(|_| rng.gen()).collect();
+        let hunks = diff(&old, &new);
+        assert_eq!(apply(&old, &new, &hunks), new);
+    }
+}
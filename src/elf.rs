@@ -0,0 +1,348 @@
+use crate::position::parse_number;
+use failure::{bail, Error};
+use goblin::elf::{header, program_header, section_header, sym};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+struct Section {
+    name: String,
+    offset: usize,
+    size: usize,
+    nobits: bool,
+}
+
+/// A `PT_LOAD` segment, which maps file content to virtual addresses.
+struct Segment {
+    vaddr: usize,
+    offset: usize,
+    filesz: usize,
+    memsz: usize,
+}
+
+struct Symbol {
+    name: String,
+    value: usize,
+    size: usize,
+    /// Index of the section `value` is relative to (relocatable files only).
+    section: Option<usize>,
+}
+
+/// The parts of an ELF file which are needed to translate sections, symbols
+/// and virtual addresses to file offsets.
+pub(crate) struct Elf {
+    sections: Vec<Section>,
+    segments: Vec<Segment>,
+    symbols: Vec<Symbol>,
+}
+
+/// Checks if `arg` is a position which refers to an ELF file, i.e. it starts
+/// with `elf:` or `vaddr:`.
+pub(crate) fn is_reference(arg: &str) -> bool {
+    arg.starts_with("elf:") || arg.starts_with("vaddr:")
+}
+
+impl Elf {
+    pub(crate) fn parse(data: &[u8]) -> Result<Elf, Error> {
+        let elf = match goblin::elf::Elf::parse(data) {
+            Ok(elf) => elf,
+            Err(e) => bail!("No valid ELF file: {}", e),
+        };
+        let sections = elf
+            .section_headers
+            .iter()
+            .map(|sh| Section {
+                name: match elf.shdr_strtab.get(sh.sh_name) {
+                    Some(Ok(name)) => name.to_string(),
+                    _ => String::new(),
+                },
+                offset: sh.sh_offset as usize,
+                size: sh.sh_size as usize,
+                nobits: sh.sh_type == section_header::SHT_NOBITS,
+            })
+            .collect();
+        let segments = elf
+            .program_headers
+            .iter()
+            .filter(|ph| ph.p_type == program_header::PT_LOAD)
+            .map(|ph| Segment {
+                vaddr: ph.p_vaddr as usize,
+                offset: ph.p_offset as usize,
+                filesz: ph.p_filesz as usize,
+                memsz: ph.p_memsz as usize,
+            })
+            .collect();
+
+        let relocatable = elf.header.e_type == header::ET_REL;
+        let thumb = elf.header.e_machine == header::EM_ARM;
+        let mut symbols = vec![];
+        for (symtab, strtab) in &[(&elf.syms, &elf.strtab), (&elf.dynsyms, &elf.dynstrtab)] {
+            for s in symtab.iter() {
+                let name = match strtab.get(s.st_name) {
+                    Some(Ok(name)) if !name.is_empty() => name.to_string(),
+                    _ => continue,
+                };
+                if s.st_shndx == section_header::SHN_UNDEF as usize {
+                    continue;
+                }
+                let mut value = s.st_value as usize;
+                // the lowest bit of ARM functions selects the Thumb instruction set
+                if thumb && s.st_type() == sym::STT_FUNC {
+                    value &= !1;
+                }
+                symbols.push(Symbol {
+                    name,
+                    value,
+                    size: s.st_size as usize,
+                    section: if relocatable { Some(s.st_shndx) } else { None },
+                });
+            }
+        }
+
+        Ok(Elf {
+            sections,
+            segments,
+            symbols,
+        })
+    }
+
+    /// Translates the virtual address `vaddr` to a file offset.
+    fn vaddr_offset(&self, vaddr: usize) -> Result<usize, Error> {
+        for s in &self.segments {
+            if vaddr >= s.vaddr && vaddr < s.vaddr + s.filesz {
+                return Ok(s.offset + vaddr - s.vaddr);
+            } else if vaddr >= s.vaddr && vaddr < s.vaddr + s.memsz {
+                bail!("The virtual address {:#x} is not backed by the file", vaddr)
+            }
+        }
+        bail!("The virtual address {:#x} is in no loadable segment", vaddr)
+    }
+
+    fn section(&self, name: &str) -> Result<&Section, Error> {
+        let section = match self.sections.iter().find(|s| s.name == name) {
+            Some(section) => section,
+            None => bail!("There is no section '{}'", name),
+        };
+        if section.nobits {
+            bail!("The section '{}' has no content in the file", name)
+        }
+        Ok(section)
+    }
+
+    fn symbol_offset(&self, symbol: &Symbol, offset: usize) -> Result<usize, Error> {
+        match symbol.section {
+            Some(index) => match self.sections.get(index) {
+                Some(section) if !section.nobits => Ok(section.offset + symbol.value + offset),
+                _ => bail!("The symbol '{}' has no content in the file", symbol.name),
+            },
+            None => self.vaddr_offset(symbol.value + offset),
+        }
+    }
+
+    /// Resolves the reference at the beginning of `position` to a file offset,
+    /// `+<offset>` directly behind the reference is added. Returns the offset,
+    /// the size of the referenced section or symbol (if no offset is given)
+    /// and the remainder of `position`.
+    fn resolve_reference<'a>(
+        &self,
+        position: &'a str,
+    ) -> Result<(usize, Option<usize>, &'a str), Error> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(
+                r"^(?:elf:sym:(?P<sym>[^+:=]+)|elf:(?P<section>[^+:=]+)|vaddr:(?P<vaddr>0[xX][[:xdigit:]]+|\d+))(?:\+(?P<offset>0[xX][[:xdigit:]]+|\d+[kmgtpeibKMGTPEIB]{0,3}))?(?P<rest>.*)$"
+            )
+            .unwrap();
+        }
+
+        let caps = match RE.captures(position) {
+            Some(caps) => caps,
+            None => bail!("Unable to parse ELF reference in <POSITION>: {}", position),
+        };
+        let offset = match caps.name("offset") {
+            Some(offset) => Some(parse_number(offset.as_str())?),
+            None => None,
+        };
+        let rest = caps.name("rest").unwrap().as_str();
+        let (begin, size) = if let Some(name) = caps.name("sym") {
+            let symbol = match self.symbols.iter().find(|s| s.name == name.as_str()) {
+                Some(symbol) => symbol,
+                None => bail!("There is no symbol '{}'", name.as_str()),
+            };
+            let begin = self.symbol_offset(symbol, offset.unwrap_or(0))?;
+            (begin, symbol.size)
+        } else if let Some(name) = caps.name("section") {
+            let section = self.section(name.as_str())?;
+            (section.offset + offset.unwrap_or(0), section.size)
+        } else {
+            let vaddr = parse_number(caps.name("vaddr").unwrap().as_str())?;
+            (self.vaddr_offset(vaddr + offset.unwrap_or(0))?, 0)
+        };
+        let size = if offset.is_none() && size > 0 {
+            Some(size)
+        } else {
+            None
+        };
+        Ok((begin, size, rest))
+    }
+
+    /// Resolves the sole reference `offset` to a file offset.
+    pub(crate) fn resolve_offset(&self, offset: &str) -> Result<usize, Error> {
+        let (begin, _, rest) = self.resolve_reference(offset)?;
+        if !rest.is_empty() {
+            bail!("Unable to parse ELF reference in offset: {}", offset)
+        }
+        Ok(begin)
+    }
+
+    /// Converts a position with ELF references to a plain position. A sole
+    /// section or symbol without offset covers the whole section or symbol,
+    /// the end may be a reference too.
+    pub(crate) fn resolve_position(&self, position: &str) -> Result<String, Error> {
+        let (begin, size, rest) = self.resolve_reference(position)?;
+        if rest.is_empty() {
+            return Ok(match size {
+                Some(size) => format!("{}+{}", begin, size),
+                None => begin.to_string(),
+            });
+        }
+        let behavior = if rest.starts_with(":=") {
+            ":="
+        } else if rest.starts_with(':') {
+            ":"
+        } else if rest.starts_with('+') {
+            "+"
+        } else {
+            bail!("Unable to parse ELF reference in <POSITION>: {}", position)
+        };
+        let to = &rest[behavior.len()..];
+        if behavior != "+" && is_reference(to) {
+            let (end, _, rest) = self.resolve_reference(to)?;
+            if !rest.is_empty() {
+                bail!("Unable to parse ELF reference in <POSITION>: {}", position)
+            }
+            Ok(format!("{}{}{}", begin, behavior, end))
+        } else {
+            Ok(format!("{}{}{}", begin, behavior, to))
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn put16(data: &mut Vec<u8>, value: u16) {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put32(data: &mut Vec<u8>, value: u32) {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Creates a 32-bit ARM executable, which is loaded at 0x08004000 with
+    /// `.text` at offset 0x60, `.rodata` at 0x70 and `.bss` behind.
+    pub(crate) fn elf() -> Vec<u8> {
+        let strtab = b"\0version_string\0main\0";
+        let shstrtab = b"\0.text\0.rodata\0.bss\0.symtab\0.strtab\0.shstrtab\0";
+        let symtab = 0x80;
+        let shoff = (symtab + 48 + strtab.len() + shstrtab.len() + 3) & !3;
+
+        let mut data = b"\x7fELF\x01\x01\x01".to_vec();
+        data.resize(16, 0);
+        put16(&mut data, 2); // ET_EXEC
+        put16(&mut data, 40); // EM_ARM
+        put32(&mut data, 1);
+        put32(&mut data, 0x0800_4061);
+        put32(&mut data, 52);
+        put32(&mut data, shoff as u32);
+        put32(&mut data, 0);
+        for value in &[52, 32, 1, 40, 7, 6] {
+            put16(&mut data, *value);
+        }
+
+        // PT_LOAD: type, offset, vaddr, paddr, filesz, memsz, flags, align
+        for value in &[1, 0, 0x0800_4000, 0x0800_4000, 0x80, 0x180, 7, 0x1000] {
+            put32(&mut data, *value);
+        }
+        data.resize(0x60, 0);
+        data.extend_from_slice(b"TEXTTEXTTEXTTEXT");
+        data.extend_from_slice(b"version 1.2.3\0\0\0");
+
+        // symbols: name, value, size, info, other, shndx
+        data.resize(symtab + 16, 0);
+        for (name, value, size, info, shndx) in &[
+            (1, 0x0800_4070, 14, 0x11, 2),
+            (16, 0x0800_4061, 16, 0x12, 1),
+        ] {
+            put32(&mut data, *name);
+            put32(&mut data, *value);
+            put32(&mut data, *size);
+            data.extend_from_slice(&[*info, 0]);
+            put16(&mut data, *shndx);
+        }
+        data.extend_from_slice(strtab);
+        data.extend_from_slice(shstrtab);
+        data.resize(shoff, 0);
+
+        // sections: name, type, flags, addr, offset, size, link, info, align, entsize
+        let shstr = symtab + 48 + strtab.len();
+        for header in &[
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [1, 1, 6, 0x0800_4060, 0x60, 16, 0, 0, 4, 0],
+            [7, 1, 2, 0x0800_4070, 0x70, 16, 0, 0, 4, 0],
+            [15, 8, 3, 0x0800_4080, 0x80, 0x100, 0, 0, 4, 0],
+            [20, 2, 0, 0, symtab, 48, 5, 1, 4, 16],
+            [28, 3, 0, 0, symtab + 48, strtab.len(), 0, 0, 1, 0],
+            [36, 3, 0, 0, shstr, shstrtab.len(), 0, 0, 1, 0],
+        ] {
+            for value in header.iter() {
+                put32(&mut data, *value as u32);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_resolve() {
+        let elf = Elf::parse(&elf()).unwrap();
+        for (position, exp) in vec![
+            ("elf:.rodata", "112+16"),
+            ("elf:.rodata+0x10", "128"),
+            ("elf:.rodata+2+4", "114+4"),
+            ("elf:.text:elf:.rodata", "96:112"),
+            ("elf:.text:elf:.rodata+1", "96:113"),
+            ("elf:.text:=0x70", "96:=0x70"),
+            ("elf:sym:version_string", "112+14"),
+            ("elf:sym:version_string+4", "116"),
+            ("elf:sym:main+0:elf:sym:version_string", "96:112"),
+            ("vaddr:0x08004000", "0"),
+            ("vaddr:0x08004070+8+2", "120+2"),
+            ("vaddr:0x0800407f", "127"),
+        ] {
+            assert_eq!(elf.resolve_position(position).unwrap(), exp);
+        }
+
+        for position in vec![
+            "elf:.bss",
+            "elf:.data",
+            "elf:sym:missing",
+            "vaddr:0x08004080",
+            "vaddr:0x08005000",
+            "vaddr:0x1000-1",
+            "elf:.text:elf:.rodata+1+2",
+            "elf:",
+        ] {
+            assert!(elf.resolve_position(position).is_err());
+        }
+    }
+
+    #[test]
+    fn test_offset() {
+        let elf = Elf::parse(&elf()).unwrap();
+        assert_eq!(elf.resolve_offset("elf:.rodata").unwrap(), 112);
+        assert_eq!(elf.resolve_offset("elf:sym:version_string+4").unwrap(), 116);
+        assert!(elf.resolve_offset("elf:.rodata+2+4").is_err());
+        assert!(elf.resolve_offset("elf:.text=x.bin").is_err());
+        assert!(is_reference("vaddr:0"));
+        assert!(!is_reference("0x100"));
+    }
+}
@@ -0,0 +1,326 @@
+use failure::{bail, Error};
+use std::str::FromStr;
+
+static HEX_CHARS: &[u8; 16] = b"0123456789abcdef";
+static BASE64_CHARS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+static BASE32_CHARS: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Text encoding of binary data.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Encoding {
+    Hex,
+    Base64,
+    Base32,
+    Ascii85,
+}
+
+impl FromStr for Encoding {
+    type Err = Error;
+
+    fn from_str(encoding: &str) -> Result<Encoding, Error> {
+        Ok(match encoding {
+            "hex" => Encoding::Hex,
+            "b64" | "base64" => Encoding::Base64,
+            "b32" | "base32" => Encoding::Base32,
+            "a85" | "ascii85" => Encoding::Ascii85,
+            x => bail!("Unknown encoding: {}", x),
+        })
+    }
+}
+
+impl Encoding {
+    pub(crate) fn variants() -> &'static [&'static str] {
+        &["hex", "b64", "base64", "b32", "base32", "a85", "ascii85"]
+    }
+
+    pub(crate) fn encode(self, data: &[u8]) -> String {
+        match self {
+            Encoding::Hex => hex_encode(data),
+            Encoding::Base64 => base64_encode(data),
+            Encoding::Base32 => base32_encode(data),
+            Encoding::Ascii85 => ascii85_encode(data),
+        }
+    }
+
+    /// Decodes `text`, whitespace (e.g. line breaks) is ignored.
+    pub(crate) fn decode(self, text: &[u8]) -> Result<Vec<u8>, Error> {
+        let text = std::str::from_utf8(text)?;
+        match self {
+            Encoding::Hex => hex_decode(text),
+            Encoding::Base64 => base64_decode(text),
+            Encoding::Base32 => base32_decode(text),
+            Encoding::Ascii85 => ascii85_decode(text),
+        }
+    }
+}
+
+/// Encodes `data` as lowercase hex string.
+pub(crate) fn hex_encode(data: &[u8]) -> String {
+    let mut s = String::with_capacity(data.len() * 2);
+    for b in data {
+        s.push(HEX_CHARS[(b >> 4) as usize] as char);
+        s.push(HEX_CHARS[(b & 0xf) as usize] as char);
+    }
+    s
+}
+
+/// Decodes a hex string, whitespace between the digits is ignored.
+pub(crate) fn hex_decode(s: &str) -> Result<Vec<u8>, Error> {
+    let digits = s
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c.to_digit(16) {
+            Some(d) => Ok(d as u8),
+            None => bail!("Invalid hex digit '{}'", c),
+        })
+        .collect::<Result<Vec<u8>, Error>>()?;
+    if digits.len() % 2 != 0 {
+        bail!("Odd number of hex digits")
+    }
+    Ok(digits.chunks(2).map(|d| d[0] << 4 | d[1]).collect())
+}
+
+/// Encodes `data` as base64 (RFC 4648) including padding.
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    let mut s = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | ((*b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(BASE64_CHARS[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+/// Decodes the digits of a base64/base32 `text` with `bits` bits per digit,
+/// the padding `=` is optional.
+fn decode_bits(text: &str, chars: &[u8], bits: u32) -> Result<Vec<u8>, Error> {
+    let mut data = vec![];
+    let mut acc = 0u32;
+    let mut n = 0;
+    let mut digits = 0;
+    let text = text.trim_end_matches(|c: char| c == '=' || c.is_whitespace());
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        let value = match chars.iter().position(|x| *x as char == c) {
+            Some(value) => value as u32,
+            None => bail!("Invalid character '{}'", c),
+        };
+        acc = (acc << bits | value) & 0xffff;
+        n += bits;
+        digits += 1;
+        if n >= 8 {
+            n -= 8;
+            data.push((acc >> n) as u8);
+        }
+    }
+    // a trailing digit has to complete a byte
+    if n >= bits || (digits > 0 && n > 0 && acc & ((1 << n) - 1) != 0) {
+        bail!("Invalid length or trailing bits")
+    }
+    Ok(data)
+}
+
+/// Decodes base64 (RFC 4648), whitespace is ignored.
+pub(crate) fn base64_decode(text: &str) -> Result<Vec<u8>, Error> {
+    decode_bits(text, BASE64_CHARS, 6)
+}
+
+/// Encodes `data` as base32 (RFC 4648) including padding.
+pub(crate) fn base32_encode(data: &[u8]) -> String {
+    let mut s = String::new();
+    for chunk in data.chunks(5) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u64, |n, (i, b)| n | ((*b as u64) << (32 - 8 * i)));
+        let digits = [0, 2, 4, 5, 7, 8][chunk.len()];
+        for i in 0..8 {
+            if i < digits {
+                s.push(BASE32_CHARS[((n >> (35 - 5 * i)) & 0x1f) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+/// Decodes base32 (RFC 4648) in upper or lower case, whitespace is ignored.
+pub(crate) fn base32_decode(text: &str) -> Result<Vec<u8>, Error> {
+    decode_bits(&text.to_ascii_uppercase(), BASE32_CHARS, 5)
+}
+
+/// Encodes `data` as Ascii85 with `z` for groups of zeros, without the `<~`
+/// and `~>` delimiters.
+pub(crate) fn ascii85_encode(data: &[u8]) -> String {
+    let mut s = String::new();
+    for chunk in data.chunks(4) {
+        if chunk == [0, 0, 0, 0] {
+            s.push('z');
+            continue;
+        }
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | ((*b as u32) << (24 - 8 * i)));
+        let mut digits = [0u8; 5];
+        let mut rest = n;
+        for digit in digits.iter_mut().rev() {
+            *digit = (rest % 85) as u8 + b'!';
+            rest /= 85;
+        }
+        for digit in &digits[..chunk.len() + 1] {
+            s.push(*digit as char);
+        }
+    }
+    s
+}
+
+/// Decodes Ascii85, optionally enclosed in `<~` and `~>`, whitespace is
+/// ignored.
+pub(crate) fn ascii85_decode(text: &str) -> Result<Vec<u8>, Error> {
+    let text = text.trim();
+    let text = text.strip_prefix("<~").unwrap_or(text);
+    let text = text.strip_suffix("~>").unwrap_or(text);
+    let mut data = vec![];
+    let mut group = vec![];
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        match c {
+            'z' if group.is_empty() => data.extend_from_slice(&[0; 4]),
+            '!'..='u' => group.push(c as u8 - b'!'),
+            _ => bail!("Invalid character '{}'", c),
+        }
+        if group.len() == 5 {
+            data.extend_from_slice(&ascii85_group(&group)?);
+            group.clear();
+        }
+    }
+    match group.len() {
+        0 => (),
+        1 => bail!("Invalid length"),
+        len => {
+            // pad with the highest digit and drop the padded bytes
+            group.resize(5, 84);
+            data.extend_from_slice(&ascii85_group(&group)?[..len - 1]);
+        }
+    }
+    Ok(data)
+}
+
+fn ascii85_group(group: &[u8]) -> Result<[u8; 4], Error> {
+    let n = group.iter().fold(0u64, |n, d| n * 85 + *d as u64);
+    if n > u32::MAX as u64 {
+        bail!("Invalid group")
+    }
+    Ok((n as u32).to_be_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(hex_encode(&[]), "");
+        assert_eq!(hex_encode(&[0x00, 0x5a, 0xff]), "005aff");
+        assert_eq!(hex_decode("005aFF").unwrap(), vec![0x00, 0x5a, 0xff]);
+        assert_eq!(
+            hex_decode("de ad\nbe ef").unwrap(),
+            vec![0xde, 0xad, 0xbe, 0xef]
+        );
+        assert!(hex_decode("5").is_err());
+        assert!(hex_decode("5g").is_err());
+    }
+
+    #[test]
+    fn test_base64() {
+        let test_vec = vec![
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (data, exp) in test_vec {
+            assert_eq!(base64_encode(data.as_bytes()), exp);
+        }
+    }
+
+    #[test]
+    fn test_base64_decode() {
+        for (exp, text) in vec![
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg"),
+            ("foobar", "Zm9v\nYmFy\n"),
+        ] {
+            assert_eq!(base64_decode(text).unwrap(), exp.as_bytes());
+        }
+        for text in vec!["Z", "Zm9vY", "Zh==", "Zm-v"] {
+            assert!(base64_decode(text).is_err());
+        }
+    }
+
+    #[test]
+    fn test_base32() {
+        let test_vec = vec![
+            ("", ""),
+            ("f", "MY======"),
+            ("fo", "MZXQ===="),
+            ("foo", "MZXW6==="),
+            ("foob", "MZXW6YQ="),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI======"),
+        ];
+        for (data, exp) in test_vec {
+            assert_eq!(base32_encode(data.as_bytes()), exp);
+            assert_eq!(base32_decode(exp).unwrap(), data.as_bytes());
+            assert_eq!(base32_decode(&exp.to_lowercase()).unwrap(), data.as_bytes());
+        }
+        assert!(base32_decode("M").is_err());
+        assert!(base32_decode("M1").is_err());
+    }
+
+    #[test]
+    fn test_ascii85() {
+        let test_vec = vec![
+            (&b""[..], ""),
+            (b"Man ", "9jqo^"),
+            (b"Man", "9jqo"),
+            (b"sure.", "F*2M7/c"),
+            (b"\0\0\0\0\0", "z!!"),
+        ];
+        for (data, exp) in test_vec {
+            assert_eq!(ascii85_encode(data), exp);
+            assert_eq!(ascii85_decode(exp).unwrap(), data);
+        }
+        assert_eq!(ascii85_decode("<~9jqo^\nF*2M7/c~>").unwrap(), b"Man sure.");
+        for text in vec!["9", "s8W-\"", "9jqo^v", "9jz"] {
+            assert!(ascii85_decode(text).is_err());
+        }
+    }
+
+    #[test]
+    fn test_encoding() {
+        for name in Encoding::variants() {
+            let encoding = name.parse::<Encoding>().unwrap();
+            let text = encoding.encode(b"\x00\x01binary\xff");
+            assert_eq!(
+                encoding.decode(text.as_bytes()).unwrap(),
+                b"\x00\x01binary\xff"
+            );
+        }
+    }
+}
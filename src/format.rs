@@ -0,0 +1,154 @@
+use crate::encoding;
+use failure::{bail, Error};
+use std::str::FromStr;
+
+static BYTES_PER_LINE: usize = 12;
+
+/// Representation which is used to print extracted bytes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Format {
+    Raw,
+    Hex,
+    Base64,
+    C,
+    Rust,
+    Python,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<Format, Error> {
+        Ok(match format {
+            "raw" => Format::Raw,
+            "hex" => Format::Hex,
+            "base64" => Format::Base64,
+            "c" => Format::C,
+            "rust" => Format::Rust,
+            "python" => Format::Python,
+            "json" => Format::Json,
+            x => bail!("Unknown format: {}", x),
+        })
+    }
+}
+
+impl Format {
+    pub(crate) fn variants() -> &'static [&'static str] {
+        &["raw", "hex", "base64", "c", "rust", "python", "json"]
+    }
+
+    /// Writes `data` in this format to `out`, `name` is used as identifier
+    /// for the source code formats.
+    pub(crate) fn write(
+        self,
+        data: &[u8],
+        name: &str,
+        out: &mut dyn std::io::Write,
+    ) -> Result<(), Error> {
+        match self {
+            Format::Raw => out.write_all(data)?,
+            Format::Hex => writeln!(out, "{}", encoding::hex_encode(data))?,
+            Format::Base64 => writeln!(out, "{}", encoding::base64_encode(data))?,
+            Format::C => {
+                writeln!(out, "const uint8_t {}[] = {{", name)?;
+                if data.is_empty() {
+                    // C has no empty arrays, the length is still 0
+                    writeln!(out, "    0x00,")?;
+                }
+                write_lines(data, out)?;
+                writeln!(out, "}};")?;
+                writeln!(out, "const size_t {}_len = {};", name, data.len())?;
+            }
+            Format::Rust => {
+                writeln!(
+                    out,
+                    "pub const {}: [u8; {}] = [",
+                    name.to_uppercase(),
+                    data.len()
+                )?;
+                write_lines(data, out)?;
+                writeln!(out, "];")?;
+            }
+            Format::Python => {
+                writeln!(out, "{} = bytes([", name)?;
+                write_lines(data, out)?;
+                writeln!(out, "])")?;
+            }
+            Format::Json => {
+                let values: Vec<String> = data.iter().map(|b| b.to_string()).collect();
+                writeln!(out, "[{}]", values.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_lines(data: &[u8], out: &mut dyn std::io::Write) -> Result<(), Error> {
+    for line in data.chunks(BYTES_PER_LINE) {
+        let values: Vec<String> = line.iter().map(|b| format!("{:#04x}", b)).collect();
+        writeln!(out, "    {},", values.join(", "))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_bytes(format: &str, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        let format = format.parse::<Format>().unwrap();
+        format.write(data, "blob", &mut out).unwrap();
+        out
+    }
+
+    fn render(format: &str, data: &[u8]) -> String {
+        String::from_utf8(render_bytes(format, data)).unwrap()
+    }
+
+    #[test]
+    fn test_formats() {
+        let data = [0x00, 0x0a, 0xff];
+        assert_eq!(render_bytes("raw", &data), data.to_vec());
+        assert_eq!(render("hex", &data), "000aff\n");
+        assert_eq!(render("base64", &data), "AAr/\n");
+        assert_eq!(
+            render("c", &data),
+            "const uint8_t blob[] = {\n    0x00, 0x0a, 0xff,\n};\nconst size_t blob_len = 3;\n"
+        );
+        assert_eq!(
+            render("rust", &data),
+            "pub const BLOB: [u8; 3] = [\n    0x00, 0x0a, 0xff,\n];\n"
+        );
+        assert_eq!(
+            render("python", &data),
+            "blob = bytes([\n    0x00, 0x0a, 0xff,\n])\n"
+        );
+        assert_eq!(render("json", &data), "[0, 10, 255]\n");
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(
+            render("c", &[]),
+            "const uint8_t blob[] = {\n    0x00,\n};\nconst size_t blob_len = 0;\n"
+        );
+        assert_eq!(render("rust", &[]), "pub const BLOB: [u8; 0] = [\n];\n");
+        assert_eq!(render("json", &[]), "[]\n");
+    }
+
+    #[test]
+    fn test_line_wrap() {
+        let data: Vec<u8> = (0..13).collect();
+        let out = render("rust", &data);
+        assert_eq!(out.lines().count(), 4);
+        assert!(out.contains("    0x0c,\n"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!("foo".parse::<Format>().is_err());
+        assert!("".parse::<Format>().is_err());
+    }
+}
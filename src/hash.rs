@@ -0,0 +1,156 @@
+use crate::checksum::{adler32_update, crc32_update, Crc, CrcHasher};
+use failure::{bail, Error};
+use sha1::Digest;
+use std::str::FromStr;
+
+/// Supported checksum and digest algorithms.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Algorithm {
+    Crc32,
+    Crc16Ccitt,
+    Adler32,
+    Md5,
+    Sha1,
+    Sha256,
+    Blake3,
+    /// CRC preset or custom definition, see `--crc`.
+    Crc(Crc),
+}
+
+impl FromStr for Algorithm {
+    type Err = Error;
+
+    fn from_str(algo: &str) -> Result<Algorithm, Error> {
+        Ok(match algo {
+            "crc32" => Algorithm::Crc32,
+            "crc16-ccitt" => Algorithm::Crc16Ccitt,
+            "adler32" => Algorithm::Adler32,
+            "md5" => Algorithm::Md5,
+            "sha1" => Algorithm::Sha1,
+            "sha256" => Algorithm::Sha256,
+            "blake3" => Algorithm::Blake3,
+            x => bail!("Unknown hash algorithm: {}", x),
+        })
+    }
+}
+
+impl Algorithm {
+    pub(crate) fn variants() -> &'static [&'static str] {
+        &[
+            "crc32",
+            "crc16-ccitt",
+            "adler32",
+            "md5",
+            "sha1",
+            "sha256",
+            "blake3",
+        ]
+    }
+
+    pub(crate) fn hasher(self) -> Hasher {
+        match self {
+            Algorithm::Crc32 => Hasher::Crc32(0),
+            Algorithm::Crc16Ccitt => Hasher::Crc(Crc::preset("CRC-16/IBM-3740").unwrap().hasher()),
+            Algorithm::Adler32 => Hasher::Adler32(1),
+            Algorithm::Md5 => Hasher::Md5(md5::Context::new()),
+            Algorithm::Sha1 => Hasher::Sha1(sha1::Sha1::new()),
+            Algorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            Algorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            Algorithm::Crc(crc) => Hasher::Crc(crc.hasher()),
+        }
+    }
+}
+
+/// Running computation of an `Algorithm`, data is fed by writing to it.
+pub(crate) enum Hasher {
+    Crc32(u32),
+    Adler32(u32),
+    Md5(md5::Context),
+    Sha1(sha1::Sha1),
+    Sha256(sha2::Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Crc(CrcHasher),
+}
+
+impl Hasher {
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Crc32(crc) => *crc = crc32_update(*crc, data),
+            Hasher::Adler32(adler) => *adler = adler32_update(*adler, data),
+            Hasher::Md5(h) => h.consume(data),
+            Hasher::Sha1(h) => h.input(data),
+            Hasher::Sha256(h) => h.input(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+            Hasher::Crc(h) => h.update(data),
+        }
+    }
+
+    /// Returns the digest, checksums are big endian.
+    pub(crate) fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Crc32(crc) => crc.to_be_bytes().to_vec(),
+            Hasher::Adler32(adler) => adler.to_be_bytes().to_vec(),
+            Hasher::Md5(h) => h.compute().0.to_vec(),
+            Hasher::Sha1(h) => h.result().to_vec(),
+            Hasher::Sha256(h) => h.result().to_vec(),
+            Hasher::Blake3(h) => h.finalize().as_bytes().to_vec(),
+            Hasher::Crc(h) => {
+                let value = h.finish().to_be_bytes();
+                value[8 - h.len()..].to_vec()
+            }
+        }
+    }
+}
+
+impl std::io::Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::hex_encode;
+
+    #[test]
+    fn test_algorithms() {
+        let expected = [
+            "cbf43926",
+            "29b1",
+            "091e01de",
+            "25f9e794323b453885f5181f1b624d0b",
+            "f7c3bc1d808e04732adf679965ccc34ca7ae3441",
+            "15e2b0d3c33891ebb0f1ef609ec419420c20e320ce94c65fbc8c3312448eb225",
+        ];
+        for (algo, exp) in Algorithm::variants().iter().zip(expected.iter()) {
+            let algo = algo.parse::<Algorithm>().unwrap();
+            let mut h = algo.hasher();
+            h.update(b"1234");
+            h.update(b"56789");
+            assert_eq!(hex_encode(&h.finish()), *exp, "{:?}", algo);
+        }
+
+        assert_eq!(
+            hex_encode(&Algorithm::Blake3.hasher().finish()),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+
+        for (name, exp) in vec![
+            ("CRC-8", "f4"),
+            ("CRC-16/MODBUS", "4b37"),
+            ("CRC-64/XZ", "995dc9bbdf1939fa"),
+        ] {
+            let mut h = Algorithm::Crc(name.parse().unwrap()).hasher();
+            h.update(b"123456789");
+            assert_eq!(hex_encode(&h.finish()), exp);
+        }
+    }
+}
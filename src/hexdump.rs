@@ -0,0 +1,182 @@
+use failure::{bail, format_err, Error};
+
+/// A contiguous run of bytes taken from a text dump, located at `offset`.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Chunk {
+    pub(crate) offset: usize,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Chunk {
+    fn end(&self) -> usize {
+        self.offset + self.data.len()
+    }
+}
+
+/// Parses a `xxd` or `hexdump -C` style text dump. The returned chunks are
+/// ordered by offset and do not overlap.
+pub(crate) fn parse(text: &str) -> Result<Vec<Chunk>, Error> {
+    let mut chunks: Vec<Chunk> = vec![];
+    let mut previous: Vec<u8> = vec![];
+    let mut squeezed = false;
+
+    for (nr, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        // hexdump squeezes identical lines into a single '*'
+        if line == "*" {
+            squeezed = true;
+            continue;
+        }
+
+        let (offset, data) =
+            parse_line(line).map_err(|e| format_err!("Line {} of dump: {}", nr + 1, e))?;
+
+        if squeezed {
+            if let Some(last) = chunks.last_mut() {
+                while !previous.is_empty() && last.end() < offset {
+                    let n = std::cmp::min(previous.len(), offset - last.end());
+                    last.data.extend_from_slice(&previous[0..n]);
+                }
+            }
+            squeezed = false;
+        }
+
+        // the last line of hexdump only holds the total length
+        if data.is_empty() {
+            continue;
+        }
+
+        match chunks.last_mut() {
+            Some(last) if last.end() == offset => last.data.extend_from_slice(&data),
+            _ => chunks.push(Chunk {
+                offset,
+                data: data.clone(),
+            }),
+        }
+        previous = data;
+    }
+
+    chunks.sort_by_key(|c| c.offset);
+    for w in chunks.windows(2) {
+        if w[0].end() > w[1].offset {
+            bail!(
+                "Dump contains overlapping data at offset {:#x}",
+                w[1].offset
+            )
+        }
+    }
+    Ok(chunks)
+}
+
+/// Flattens the chunks into a byte vector starting at offset 0, gaps are
+/// filled with zeros (same as `xxd -r` does).
+pub(crate) fn to_bytes(chunks: &[Chunk]) -> Vec<u8> {
+    let len = chunks.last().map(|c| c.end()).unwrap_or(0);
+    let mut data = vec![0; len];
+    for c in chunks {
+        data[c.offset..c.end()].copy_from_slice(&c.data);
+    }
+    data
+}
+
+fn parse_line(line: &str) -> Result<(usize, Vec<u8>), Error> {
+    let digits = line
+        .find(|c: char| !c.is_ascii_hexdigit())
+        .unwrap_or(line.len());
+    if digits == 0 {
+        bail!("missing offset")
+    }
+    let offset = usize::from_str_radix(&line[0..digits], 16)?;
+    let rest = &line[digits..];
+
+    // xxd:        '00000000: 4865 6c6c 6f0a  Hello.'
+    // hexdump -C: '00000000  48 65 6c 6c 6f 0a  |Hello.|'
+    let hex = if let Some(rest) = rest.strip_prefix(':') {
+        let rest = rest.trim_start();
+        match rest.find("  ") {
+            Some(idx) => &rest[0..idx],
+            None => rest,
+        }
+    } else {
+        match rest.find('|') {
+            Some(idx) => &rest[0..idx],
+            None => rest,
+        }
+    };
+
+    let mut data = vec![];
+    for group in hex.split_whitespace() {
+        if group.len() % 2 != 0 || !group.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("invalid hex group '{}'", group)
+        }
+        for i in (0..group.len()).step_by(2) {
+            data.push(u8::from_str_radix(&group[i..i + 2], 16)?);
+        }
+    }
+    Ok((offset, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xxd() {
+        let dump = "00000000: 4865 6c6c 6f20 576f 726c 6421 2020 2020  Hello World!
+00000010: 0a41 4243                                .ABC
+";
+        let chunks = parse(dump).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(to_bytes(&chunks), b"Hello World!    \nABC".to_vec());
+    }
+
+    #[test]
+    fn test_hexdump_canonical() {
+        let dump = "00000000  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|
+*
+00000030  41 42 7c                                          |AB||
+00000033
+";
+        let chunks = parse(dump).unwrap();
+        let mut exp = vec![0; 0x30];
+        exp.extend_from_slice(b"AB|");
+        assert_eq!(to_bytes(&chunks), exp);
+    }
+
+    #[test]
+    fn test_gaps_and_order() {
+        let dump = "00000010: 4142  AB\n00000002: 43  C\n";
+        let chunks = parse(dump).unwrap();
+        assert_eq!(
+            chunks,
+            vec![
+                Chunk {
+                    offset: 2,
+                    data: vec![0x43]
+                },
+                Chunk {
+                    offset: 0x10,
+                    data: vec![0x41, 0x42]
+                }
+            ]
+        );
+        let data = to_bytes(&chunks);
+        assert_eq!(data.len(), 0x12);
+        assert_eq!(data[2], 0x43);
+    }
+
+    #[test]
+    fn test_invalid() {
+        for dump in &[
+            "foo",
+            "00000000: 4",
+            "00000000: zz",
+            "00000000: 4142\n00000001: 43",
+        ] {
+            assert!(parse(dump).is_err(), "{}", dump);
+        }
+    }
+}
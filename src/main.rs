@@ -1,67 +1,120 @@
+mod archive;
+mod checksum;
 mod cli;
 mod command;
+mod compression;
 mod defs;
+mod diff;
+mod elf;
+mod encoding;
+mod format;
+mod hash;
+mod hexdump;
+mod patch;
 mod position;
 
+use command::bitwise::{BitwiseCommand, Op};
+use position::PositionParser;
+
+fn exec<C: command::Command>(
+    runner: &command::CommandRunner,
+    cmd: Result<C, failure::Error>,
+) -> i32 {
+    match cmd {
+        Ok(mut cmd) => match runner.exec(&mut cmd) {
+            Ok(_) => exitcode::OK,
+            Err(x) => {
+                eprintln!("{}", x);
+                exitcode::SOFTWARE
+            }
+        },
+        Err(x) => {
+            eprintln!("{}", x);
+            exitcode::USAGE
+        }
+    }
+}
+
 fn main() {
     env_logger::init();
     let matches = cli::get_matches();
 
-    let runner = command::CommandRunner::from_matches(&matches).unwrap();
+    let runner = match command::CommandRunner::from_matches(&matches) {
+        Ok(runner) => runner,
+        Err(x) => {
+            eprintln!("{}", x);
+            std::process::exit(exitcode::USAGE);
+        }
+    };
+
+    // ELF references in positions are resolved with the input
+    let parser = PositionParser::new(|| runner.read_input());
 
     let exit_code = match matches.subcommand() {
-        ("delete", Some(m)) => match command::delete::DeleteCommand::from_matches(m) {
-            Ok(mut cmd) => match runner.exec(&mut cmd) {
-                Ok(_) => exitcode::OK,
-                Err(x) => {
-                    eprintln!("{}", x);
-                    exitcode::SOFTWARE
-                }
-            },
-            Err(x) => {
-                eprintln!("{}", x);
-                exitcode::USAGE
-            }
-        },
-        ("cut", Some(m)) => match command::cut::CutCommand::from_matches(m) {
-            Ok(mut cmd) => match runner.exec(&mut cmd) {
-                Ok(_) => exitcode::OK,
-                Err(x) => {
-                    eprintln!("{}", x);
-                    exitcode::SOFTWARE
-                }
-            },
-            Err(x) => {
-                eprintln!("{}", x);
-                exitcode::USAGE
-            }
-        },
-        ("add", Some(m)) => match command::add::AddCommand::from_matches(m) {
-            Ok(mut cmd) => match runner.exec(&mut cmd) {
-                Ok(_) => exitcode::OK,
-                Err(x) => {
-                    eprintln!("{}", x);
-                    exitcode::SOFTWARE
-                }
-            },
-            Err(x) => {
-                eprintln!("{}", x);
-                exitcode::USAGE
-            }
-        },
-        ("replace", Some(m)) => match command::replace::ReplaceCommand::from_matches(m) {
-            Ok(mut cmd) => match runner.exec(&mut cmd) {
-                Ok(_) => exitcode::OK,
-                Err(x) => {
-                    eprintln!("{}", x);
-                    exitcode::SOFTWARE
-                }
-            },
-            Err(x) => {
-                eprintln!("{}", x);
-                exitcode::USAGE
-            }
-        },
+        ("delete", Some(m)) => exec(
+            &runner,
+            command::delete::DeleteCommand::from_matches(m, &parser),
+        ),
+        ("cut", Some(m)) => exec(&runner, command::cut::CutCommand::from_matches(m, &parser)),
+        ("add", Some(m)) => exec(&runner, command::add::AddCommand::from_matches(m, &parser)),
+        ("replace", Some(m)) => exec(
+            &runner,
+            command::replace::ReplaceCommand::from_matches(m, &parser),
+        ),
+        ("diff", Some(m)) => exec(&runner, command::diff::DiffCommand::from_matches(m)),
+        ("delta", Some(m)) => exec(&runner, command::delta::DeltaCommand::from_matches(m)),
+        ("patch", Some(m)) => exec(&runner, command::patch::PatchCommand::from_matches(m)),
+        ("merge", Some(m)) => exec(&runner, command::merge::MergeCommand::from_matches(m)),
+        ("hash", Some(m)) => exec(
+            &runner,
+            command::hash::HashCommand::from_matches(m, &parser),
+        ),
+        ("fixup", Some(m)) => exec(
+            &runner,
+            command::fixup::FixupCommand::from_matches(m, &parser),
+        ),
+        ("split", Some(m)) => exec(&runner, command::split::SplitCommand::from_matches(m)),
+        ("join", Some(m)) => exec(&runner, command::join::JoinCommand::from_matches(m)),
+        ("overlay", Some(m)) => exec(
+            &runner,
+            command::overlay::OverlayCommand::from_matches(m, &parser),
+        ),
+        ("copy", Some(m)) => exec(
+            &runner,
+            command::copy::CopyCommand::from_matches(m, &parser),
+        ),
+        ("move", Some(m)) => exec(
+            &runner,
+            command::copy::CopyCommand::from_move_matches(m, &parser),
+        ),
+        ("swap", Some(m)) => exec(
+            &runner,
+            command::swap::SwapCommand::from_matches(m, &parser),
+        ),
+        ("transform", Some(m)) => exec(
+            &runner,
+            command::transform::TransformCommand::from_matches(m, &parser),
+        ),
+        ("xor", Some(m)) => exec(&runner, BitwiseCommand::from_matches(m, &parser, Op::Xor)),
+        ("and", Some(m)) => exec(&runner, BitwiseCommand::from_matches(m, &parser, Op::And)),
+        ("or", Some(m)) => exec(&runner, BitwiseCommand::from_matches(m, &parser, Op::Or)),
+        ("not", Some(m)) => exec(&runner, BitwiseCommand::from_matches(m, &parser, Op::Not)),
+        ("encode", Some(m)) => exec(
+            &runner,
+            command::encode::EncodeCommand::from_matches(m, &parser),
+        ),
+        ("decode", Some(m)) => exec(
+            &runner,
+            command::encode::EncodeCommand::from_decode_matches(m, &parser),
+        ),
+        ("inflate", Some(m)) => exec(
+            &runner,
+            command::inflate::InflateCommand::from_matches(m, &parser),
+        ),
+        ("deflate", Some(m)) => exec(
+            &runner,
+            command::inflate::InflateCommand::from_deflate_matches(m, &parser),
+        ),
         _ => unreachable!(),
     };
 
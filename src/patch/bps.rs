@@ -0,0 +1,214 @@
+use crate::checksum::crc32;
+use crate::patch::{read_crc32, write_number, Reader};
+use failure::{bail, Error};
+
+pub(crate) static MAGIC: &[u8] = b"BPS1";
+/// Source, target and patch CRC-32.
+static FOOTER_LEN: usize = 12;
+/// Matching source bytes shorter than this are stored as literal data.
+static MIN_SOURCE_READ: usize = 4;
+
+static SOURCE_READ: usize = 0;
+static TARGET_READ: usize = 1;
+static SOURCE_COPY: usize = 2;
+static TARGET_COPY: usize = 3;
+
+/// Applies a relative offset (lowest bit is the sign) on `base`.
+fn relative(base: usize, n: usize) -> Result<usize, Error> {
+    let offset = if n & 1 == 1 {
+        base.checked_sub(n >> 1)
+    } else {
+        base.checked_add(n >> 1)
+    };
+    offset.ok_or_else(|| failure::err_msg("Invalid relative offset in BPS patch"))
+}
+
+pub(crate) fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    if patch.len() < MAGIC.len() + FOOTER_LEN || !patch.starts_with(MAGIC) {
+        bail!("Not a BPS patch")
+    }
+    let footer = patch.len() - FOOTER_LEN;
+    if crc32(&patch[0..patch.len() - 4]) != read_crc32(patch, footer + 8) {
+        bail!("BPS patch is corrupted, checksum mismatch")
+    }
+
+    let mut r = Reader::new(&patch[0..footer]);
+    r.bytes(MAGIC.len())?;
+    let source_size = r.number()?;
+    let target_size = r.number()?;
+    let metadata_size = r.number()?;
+    r.bytes(metadata_size)?;
+    if source.len() != source_size || crc32(source) != read_crc32(patch, footer) {
+        bail!("Input does not match the source of the BPS patch")
+    }
+
+    // the target grows with the actions instead of trusting the target size
+    let mut target: Vec<u8> = vec![];
+    let mut source_offset = 0;
+    let mut target_offset = 0;
+    while r.pos() < footer {
+        let data = r.number()?;
+        let length = (data >> 2) + 1;
+        match target.len().checked_add(length) {
+            Some(end) if end <= target_size => (),
+            _ => bail!("BPS patch writes beyond the target size"),
+        }
+        match data & 3 {
+            x if x == SOURCE_READ => {
+                let begin = target.len();
+                match source.get(begin..begin + length) {
+                    Some(s) => target.extend_from_slice(s),
+                    None => bail!("BPS patch reads beyond the source"),
+                }
+            }
+            x if x == TARGET_READ => target.extend_from_slice(r.bytes(length)?),
+            x if x == SOURCE_COPY => {
+                source_offset = relative(source_offset, r.number()?)?;
+                let end = source_offset.checked_add(length);
+                match end.and_then(|end| source.get(source_offset..end)) {
+                    Some(s) => target.extend_from_slice(s),
+                    None => bail!("BPS patch copies beyond the source"),
+                }
+                source_offset += length;
+            }
+            x if x == TARGET_COPY => {
+                target_offset = relative(target_offset, r.number()?)?;
+                if target.try_reserve(length).is_err() {
+                    bail!("BPS patch copies more data than fits into memory")
+                }
+                // the copied range may overlap with the bytes being written
+                for _ in 0..length {
+                    match target.get(target_offset) {
+                        Some(b) => target.push(*b),
+                        None => bail!("BPS patch copies beyond the target"),
+                    }
+                    target_offset += 1;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if target.len() != target_size || crc32(&target) != read_crc32(patch, footer + 4) {
+        bail!("Patched data does not match the target of the BPS patch")
+    }
+    Ok(target)
+}
+
+fn push_action(patch: &mut Vec<u8>, action: usize, length: usize) {
+    write_number(patch, ((length - 1) << 2) | action);
+}
+
+/// Creates a linear BPS patch, target data either is read from the same
+/// offset of the source or stored as literal.
+pub(crate) fn create(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = MAGIC.to_vec();
+    write_number(&mut patch, source.len());
+    write_number(&mut patch, target.len());
+    write_number(&mut patch, 0);
+
+    let flush = |patch: &mut Vec<u8>, literal: &[u8]| {
+        if !literal.is_empty() {
+            push_action(patch, TARGET_READ, literal.len());
+            patch.extend_from_slice(literal);
+        }
+    };
+
+    let mut literal_begin = 0;
+    let mut i = 0;
+    while i < target.len() {
+        let n = source
+            .iter()
+            .skip(i)
+            .zip(&target[i..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if n >= MIN_SOURCE_READ || (n > 0 && i + n == target.len()) {
+            flush(&mut patch, &target[literal_begin..i]);
+            push_action(&mut patch, SOURCE_READ, n);
+            i += n;
+            literal_begin = i;
+        } else {
+            i += 1;
+        }
+    }
+    flush(&mut patch, &target[literal_begin..]);
+
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    let crc = crc32(&patch);
+    patch.extend_from_slice(&crc.to_le_bytes());
+    patch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_footer(source: &[u8], target: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut patch = body.to_vec();
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let crc = crc32(&patch);
+        patch.extend_from_slice(&crc.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn test_apply_copies() {
+        // SourceCopy 'bar', TargetRead 'X', TargetCopy 'barX' twice (overlapping)
+        let patch = with_footer(
+            b"foobar",
+            b"barXbarXbarX",
+            b"BPS1\x86\x8c\x80\x8a\x86\x81X\x9f\x80",
+        );
+        assert_eq!(apply(b"foobar", &patch).unwrap(), b"barXbarXbarX".to_vec());
+    }
+
+    #[test]
+    fn test_create() {
+        let patch = create(b"foobar", b"foobarBAR");
+        assert_eq!(
+            &patch[0..patch.len() - FOOTER_LEN],
+            b"BPS1\x86\x89\x80\x94\x89BAR"
+        );
+        assert_eq!(apply(b"foobar", &patch).unwrap(), b"foobarBAR".to_vec());
+    }
+
+    #[test]
+    fn test_verify() {
+        let patch = create(b"foobar", b"fooBAR");
+        assert!(apply(b"foobaz", &patch).is_err());
+        assert!(apply(b"fooba", &patch).is_err());
+        let patch = with_footer(b"foobar", b"foobarfoo", b"BPS1\x86\x89\x80\xa0");
+        assert!(apply(b"foobar", &patch).is_err());
+    }
+
+    #[test]
+    fn test_invalid_sizes() {
+        let header = |target_size: usize| {
+            let mut body = MAGIC.to_vec();
+            write_number(&mut body, 3);
+            write_number(&mut body, target_size);
+            write_number(&mut body, 0);
+            body
+        };
+        // no data for a huge target
+        let body = header(std::usize::MAX);
+        assert!(apply(b"foo", &with_footer(b"foo", b"", &body)).is_err());
+
+        // SourceCopy from a huge offset
+        let mut body = header(3);
+        write_number(&mut body, SOURCE_COPY);
+        write_number(&mut body, std::usize::MAX - 1);
+        assert!(apply(b"foo", &with_footer(b"foo", b"", &body)).is_err());
+
+        // TargetRead 'X', TargetCopy of 2^60 bytes
+        let mut body = header(std::usize::MAX);
+        write_number(&mut body, TARGET_READ);
+        body.push(b'X');
+        write_number(&mut body, ((1 << 60) - 1) << 2 | TARGET_COPY);
+        write_number(&mut body, 0);
+        assert!(apply(b"foo", &with_footer(b"foo", b"", &body)).is_err());
+    }
+}
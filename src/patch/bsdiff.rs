@@ -0,0 +1,375 @@
+use crate::compression::Compression;
+use failure::{bail, Error};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+pub(crate) static MAGIC: &[u8] = b"BSDIFF40";
+/// Magic, length of control and diff block, target size.
+static HEADER_LEN: usize = 32;
+
+/// Reads a 64 bit sign-magnitude little endian integer.
+fn offtin(b: &[u8]) -> i64 {
+    let mut x = [0; 8];
+    x.copy_from_slice(&b[0..8]);
+    let n = (u64::from_le_bytes(x) & !(1 << 63)) as i64;
+    if b[7] & 0x80 != 0 {
+        -n
+    } else {
+        n
+    }
+}
+
+fn offtout(n: i64, out: &mut Vec<u8>) {
+    let mut x = n.unsigned_abs().to_le_bytes();
+    if n < 0 {
+        x[7] |= 0x80;
+    }
+    out.extend_from_slice(&x);
+}
+
+fn length(n: i64) -> Result<usize, Error> {
+    if n < 0 {
+        bail!("Negative length in bsdiff patch")
+    }
+    Ok(n as usize)
+}
+
+/// Reader of a block, bzip2 compressed or taken as is.
+fn block_reader(block: &[u8], compressed: bool) -> Box<dyn Read + '_> {
+    if compressed {
+        Box::new(bzip2::read::BzDecoder::new(block))
+    } else {
+        Box::new(block)
+    }
+}
+
+fn read_block(r: &mut dyn Read, buf: &mut [u8], block: &str) -> Result<(), Error> {
+    if r.read_exact(buf).is_err() {
+        bail!("Unexpected end of bsdiff {} block", block)
+    }
+    Ok(())
+}
+
+fn compress(block: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut e = bzip2::write::BzEncoder::new(vec![], bzip2::Compression::Best);
+    e.write_all(block)?;
+    Ok(e.finish()?)
+}
+
+pub(crate) fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut target = vec![];
+    apply_from(1024, &mut std::io::Cursor::new(source), patch, &mut target)?;
+    Ok(target)
+}
+
+/// Applies `patch` on the `source` and streams the target to `out`. The
+/// source is read in pieces of `blocksize` bytes at the offsets the control
+/// block seeks to.
+pub(crate) fn apply_from<R: Read + Seek>(
+    blocksize: usize,
+    source: &mut R,
+    patch: &[u8],
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    if patch.len() < HEADER_LEN || !patch.starts_with(MAGIC) {
+        bail!("Not a bsdiff patch")
+    }
+    let ctrl_len = length(offtin(&patch[8..]))?;
+    let diff_len = length(offtin(&patch[16..]))?;
+    let target_size = length(offtin(&patch[24..]))?;
+    if patch.len() - HEADER_LEN < ctrl_len.saturating_add(diff_len) {
+        bail!("bsdiff patch is truncated")
+    }
+    let diff_begin = HEADER_LEN + ctrl_len;
+    let extra_begin = diff_begin + diff_len;
+    // all blocks are compressed if the control block has a bzip2 header,
+    // the data of uncompressed diff and extra blocks is arbitrary
+    let compressed =
+        Compression::detect(&patch[HEADER_LEN..diff_begin]) == Some(Compression::Bzip2);
+    let mut ctrl = block_reader(&patch[HEADER_LEN..diff_begin], compressed);
+    let mut diff = block_reader(&patch[diff_begin..extra_begin], compressed);
+    let mut extra = block_reader(&patch[extra_begin..], compressed);
+
+    let source_len = source.seek(SeekFrom::End(0))? as i64;
+    let mut next = || {
+        let mut x = [0; 8];
+        read_block(&mut ctrl, &mut x, "control")?;
+        Ok::<i64, Error>(offtin(&x))
+    };
+    let mut buffer = vec![0; blocksize];
+    let mut old = vec![0; blocksize];
+    let mut written = 0;
+    let mut source_pos: i64 = 0;
+    while written < target_size {
+        let add = length(next()?)?;
+        let copy = length(next()?)?;
+        let seek = next()?;
+        if written.saturating_add(add).saturating_add(copy) > target_size {
+            bail!("bsdiff patch writes beyond the target size")
+        }
+
+        let mut left = add;
+        while left > 0 {
+            let n = std::cmp::min(left, blocksize);
+            read_block(&mut diff, &mut buffer[..n], "diff")?;
+            // bytes outside of the source are 0
+            let begin = std::cmp::max(source_pos, 0);
+            let end = std::cmp::min(source_pos + n as i64, source_len);
+            old[..n].iter_mut().for_each(|b| *b = 0);
+            if begin < end {
+                source.seek(SeekFrom::Start(begin as u64))?;
+                source.read_exact(
+                    &mut old[(begin - source_pos) as usize..(end - source_pos) as usize],
+                )?;
+            }
+            for (b, s) in buffer[..n].iter_mut().zip(&old[..n]) {
+                *b = b.wrapping_add(*s);
+            }
+            out.write_all(&buffer[..n])?;
+            source_pos += n as i64;
+            left -= n;
+        }
+        if std::io::copy(&mut (&mut extra).take(copy as u64), out)? < copy as u64 {
+            bail!("Unexpected end of bsdiff extra block")
+        }
+        source_pos += seek;
+        written += add + copy;
+    }
+    Ok(())
+}
+
+/// Builds the suffix array of `data` including the empty suffix by prefix
+/// doubling.
+fn suffix_array(data: &[u8]) -> Vec<usize> {
+    let n = data.len();
+    let mut sa: Vec<usize> = (0..=n).collect();
+    let mut rank: Vec<usize> = data.iter().map(|b| *b as usize + 1).collect();
+    rank.push(0);
+    let mut tmp = vec![0; n + 1];
+    let mut k = 1;
+    loop {
+        let key = |i: usize| (rank[i], if i + k <= n { rank[i + k] } else { 0 });
+        sa.sort_unstable_by_key(|i| key(*i));
+        tmp[sa[0]] = 0;
+        for i in 1..=n {
+            tmp[sa[i]] = tmp[sa[i - 1]] + (key(sa[i - 1]) < key(sa[i])) as usize;
+        }
+        std::mem::swap(&mut rank, &mut tmp);
+        if rank[sa[n]] == n || k > n {
+            return sa;
+        }
+        k *= 2;
+    }
+}
+
+fn match_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// Binary search for the longest match of `data` in `source`, returns the
+/// position and length.
+fn search(sa: &[usize], source: &[u8], data: &[u8]) -> (usize, usize) {
+    let (mut st, mut en) = (0, sa.len() - 1);
+    while en - st >= 2 {
+        let x = st + (en - st) / 2;
+        if source[sa[x]..] < *data {
+            st = x;
+        } else {
+            en = x;
+        }
+    }
+    let x = match_len(&source[sa[st]..], data);
+    let y = match_len(&source[sa[en]..], data);
+    if x > y {
+        (sa[st], x)
+    } else {
+        (sa[en], y)
+    }
+}
+
+/// Creates a bzip2 compressed patch, following the classic bsdiff algorithm.
+pub(crate) fn create(source: &[u8], target: &[u8]) -> Result<Vec<u8>, Error> {
+    let sa = suffix_array(source);
+    let eq = |s: usize, t: usize| s < source.len() && source[s] == target[t];
+
+    let mut ctrl = vec![];
+    let mut diff = vec![];
+    let mut extra = vec![];
+
+    let (mut scan, mut len, mut pos) = (0, 0, 0);
+    let (mut last_scan, mut last_pos, mut last_offset) = (0, 0, 0_i64);
+    while scan < target.len() {
+        let mut old_score = 0_i64;
+        scan += len;
+        let mut scsc = scan;
+        while scan < target.len() {
+            let (p, l) = search(&sa, source, &target[scan..]);
+            pos = p;
+            len = l;
+            while scsc < scan + len {
+                let s = scsc as i64 + last_offset;
+                if s >= 0 && eq(s as usize, scsc) {
+                    old_score += 1;
+                }
+                scsc += 1;
+            }
+            if (len as i64 == old_score && len != 0) || len as i64 > old_score + 8 {
+                break;
+            }
+            let s = scan as i64 + last_offset;
+            if s >= 0 && eq(s as usize, scan) {
+                old_score -= 1;
+            }
+            scan += 1;
+        }
+
+        if len as i64 != old_score || scan == target.len() {
+            // extend the previous match forwards
+            let (mut s, mut sf, mut lenf) = (0_i64, 0_i64, 0);
+            let mut i = 0;
+            while last_scan + i < scan && last_pos + i < source.len() {
+                if source[last_pos + i] == target[last_scan + i] {
+                    s += 1;
+                }
+                i += 1;
+                if s * 2 - i as i64 > sf * 2 - lenf as i64 {
+                    sf = s;
+                    lenf = i;
+                }
+            }
+
+            // extend the current match backwards
+            let mut lenb = 0;
+            if scan < target.len() {
+                let (mut s, mut sb) = (0_i64, 0_i64);
+                let mut i = 1;
+                while scan >= last_scan + i && pos >= i {
+                    if source[pos - i] == target[scan - i] {
+                        s += 1;
+                    }
+                    if s * 2 - i as i64 > sb * 2 - lenb as i64 {
+                        sb = s;
+                        lenb = i;
+                    }
+                    i += 1;
+                }
+            }
+
+            // split an overlap of both extensions at the best position
+            if last_scan + lenf > scan - lenb {
+                let overlap = (last_scan + lenf) - (scan - lenb);
+                let (mut s, mut ss, mut lens) = (0_i64, 0_i64, 0);
+                for i in 0..overlap {
+                    if target[last_scan + lenf - overlap + i]
+                        == source[last_pos + lenf - overlap + i]
+                    {
+                        s += 1;
+                    }
+                    if target[scan - lenb + i] == source[pos - lenb + i] {
+                        s -= 1;
+                    }
+                    if s > ss {
+                        ss = s;
+                        lens = i + 1;
+                    }
+                }
+                lenf = lenf + lens - overlap;
+                lenb -= lens;
+            }
+
+            for i in 0..lenf {
+                diff.push(target[last_scan + i].wrapping_sub(source[last_pos + i]));
+            }
+            let extra_len = (scan - lenb) - (last_scan + lenf);
+            extra.extend_from_slice(&target[last_scan + lenf..scan - lenb]);
+
+            offtout(lenf as i64, &mut ctrl);
+            offtout(extra_len as i64, &mut ctrl);
+            offtout((pos - lenb) as i64 - (last_pos + lenf) as i64, &mut ctrl);
+
+            last_scan = scan - lenb;
+            last_pos = pos - lenb;
+            last_offset = pos as i64 - scan as i64;
+        }
+    }
+
+    let ctrl = compress(&ctrl)?;
+    let diff = compress(&diff)?;
+    let mut patch = MAGIC.to_vec();
+    offtout(ctrl.len() as i64, &mut patch);
+    offtout(diff.len() as i64, &mut patch);
+    offtout(target.len() as i64, &mut patch);
+    patch.extend_from_slice(&ctrl);
+    patch.extend_from_slice(&diff);
+    patch.extend_from_slice(&compress(&extra)?);
+    Ok(patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offt() {
+        for n in [0, 1, -1, 255, -256, 1 << 40, -(1 << 40)].iter() {
+            let mut out = vec![];
+            offtout(*n, &mut out);
+            assert_eq!(out.len(), 8);
+            assert_eq!(offtin(&out), *n);
+        }
+        assert_eq!(offtin(&[2, 0, 0, 0, 0, 0, 0, 0x80]), -2);
+    }
+
+    #[test]
+    fn test_suffix_array() {
+        assert_eq!(suffix_array(b""), vec![0]);
+        assert_eq!(suffix_array(b"banana"), vec![6, 5, 3, 1, 0, 4, 2]);
+        assert_eq!(suffix_array(b"aaaa"), vec![4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_apply_uncompressed() {
+        // add 3 on "foo", copy "BAR", seek -3, add 3 on "foo"
+        let mut ctrl = vec![];
+        for n in [3, 3, -3, 3, 0, 0].iter() {
+            offtout(*n, &mut ctrl);
+        }
+        let diff = [0, 0, 1, 0, 0, 0];
+        let mut patch = MAGIC.to_vec();
+        offtout(ctrl.len() as i64, &mut patch);
+        offtout(diff.len() as i64, &mut patch);
+        offtout(9, &mut patch);
+        patch.extend_from_slice(&ctrl);
+        patch.extend_from_slice(&diff);
+        patch.extend_from_slice(b"BAR");
+        assert_eq!(apply(b"foobar", &patch).unwrap(), b"fopBARfoo".to_vec());
+
+        patch.pop();
+        assert!(apply(b"foobar", &patch).is_err());
+
+        // an uncompressed extra block that looks like bzip2
+        patch.truncate(patch.len() - 2);
+        patch.extend_from_slice(b"BZh");
+        assert_eq!(apply(b"foobar", &patch).unwrap(), b"fopBZhfoo".to_vec());
+    }
+
+    #[test]
+    fn test_create() {
+        let source: Vec<u8> = (0..4096).map(|i| (i * 7 % 251) as u8).collect();
+        let mut target = source.clone();
+        target[100] ^= 0xff;
+        target.splice(2000..2000, b"inserted".iter().cloned());
+        target.truncate(4000);
+        let patch = create(&source, &target).unwrap();
+        assert_eq!(
+            Compression::detect(&patch[HEADER_LEN..]),
+            Some(Compression::Bzip2)
+        );
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+        for bs in vec![1, 2, 3, 4, 10] {
+            let mut out = vec![];
+            let mut r = std::io::Cursor::new(&source);
+            assert!(apply_from(bs, &mut r, &patch, &mut out).is_ok());
+            assert_eq!(out, target);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::patch::Reader;
+use failure::{bail, Error};
+
+pub(crate) static MAGIC: &[u8] = b"PATCH";
+static FOOTER: &[u8] = b"EOF";
+/// IPS offsets are 24 bit wide.
+static MAX_OFFSET: usize = 0xff_ffff;
+static MAX_RECORD: usize = 0xffff;
+/// Runs of the same byte which are at least this long are RLE encoded.
+static MIN_RLE: usize = 8;
+
+fn be(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |n, b| (n << 8) | *b as usize)
+}
+
+fn write(data: &mut Vec<u8>, offset: usize, value: &[u8]) {
+    if data.len() < offset + value.len() {
+        data.resize(offset + value.len(), 0);
+    }
+    data[offset..offset + value.len()].copy_from_slice(value);
+}
+
+pub(crate) fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut r = Reader::new(patch);
+    if r.bytes(MAGIC.len())? != MAGIC {
+        bail!("Not an IPS patch")
+    }
+
+    let mut data = source.to_vec();
+    loop {
+        let offset = r.bytes(3)?;
+        if offset == FOOTER {
+            // optional truncation extension
+            if patch.len() - r.pos() >= 3 {
+                data.truncate(be(r.bytes(3)?));
+            }
+            break;
+        }
+        let offset = be(offset);
+        let size = be(r.bytes(2)?);
+        if size == 0 {
+            let run = be(r.bytes(2)?);
+            let value = r.byte()?;
+            write(&mut data, offset, &vec![value; run]);
+        } else {
+            write(&mut data, offset, r.bytes(size)?);
+        }
+    }
+    Ok(data)
+}
+
+fn push_record(patch: &mut Vec<u8>, offset: usize, value: &[u8]) {
+    patch.extend_from_slice(&offset.to_be_bytes()[std::mem::size_of::<usize>() - 3..]);
+    if value.len() >= MIN_RLE && value.iter().all(|b| *b == value[0]) {
+        patch.extend_from_slice(&[0, 0]);
+        patch.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        patch.push(value[0]);
+    } else {
+        patch.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        patch.extend_from_slice(value);
+    }
+}
+
+pub(crate) fn create(source: &[u8], target: &[u8]) -> Result<Vec<u8>, Error> {
+    if target.len() > MAX_OFFSET + 1 {
+        bail!("IPS patches are limited to targets of 16 MiB")
+    }
+
+    let differs = |i: usize| i >= source.len() || source[i] != target[i];
+    let mut patch = MAGIC.to_vec();
+    let mut i = 0;
+    while i < target.len() {
+        if !differs(i) {
+            i += 1;
+            continue;
+        }
+        // an offset equal to the footer would end the patch, start earlier
+        let mut begin = i;
+        if begin == be(FOOTER) {
+            begin -= 1;
+        }
+        let mut end = i;
+        while end < target.len() && end - begin < MAX_RECORD && differs(end) {
+            end += 1;
+        }
+        push_record(&mut patch, begin, &target[begin..end]);
+        i = end;
+    }
+
+    patch.extend_from_slice(FOOTER);
+    if target.len() < source.len() {
+        patch.extend_from_slice(&target.len().to_be_bytes()[std::mem::size_of::<usize>() - 3..]);
+    }
+    Ok(patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let patch = b"PATCH\x00\x00\x01\x00\x02AB\x00\x00\x06\x00\x00\x00\x03ZEOF";
+        assert_eq!(apply(b"foobar", patch).unwrap(), b"fABbarZZZ".to_vec());
+
+        let patch = b"PATCHEOF\x00\x00\x03";
+        assert_eq!(apply(b"foobar", patch).unwrap(), b"foo".to_vec());
+
+        assert!(apply(b"foobar", b"PATCH\x00\x00\x01\x00\x02A").is_err());
+        assert!(apply(b"foobar", b"PATCH\x00\x00\x01").is_err());
+        assert!(apply(b"foobar", b"PATCX").is_err());
+    }
+
+    #[test]
+    fn test_create() {
+        assert_eq!(
+            create(b"foobar", b"fABbarZZZZZZZZZZ").unwrap(),
+            b"PATCH\x00\x00\x01\x00\x02AB\x00\x00\x06\x00\x00\x00\x0aZEOF".to_vec()
+        );
+        assert_eq!(
+            create(b"foobar", b"foo").unwrap(),
+            b"PATCHEOF\x00\x00\x03".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_footer_offset() {
+        let source = vec![0; 0x454f50];
+        let mut target = source.clone();
+        target[0x454f46] = 1;
+        let patch = create(&source, &target).unwrap();
+        assert_eq!(&patch[5..8], &[0x45, 0x4f, 0x45]);
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+    }
+}
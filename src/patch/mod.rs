@@ -0,0 +1,209 @@
+pub(crate) mod bps;
+pub(crate) mod bsdiff;
+pub(crate) mod ips;
+pub(crate) mod ups;
+pub(crate) mod vcdiff;
+use failure::{bail, Error};
+use std::str::FromStr;
+
+/// Supported binary patch formats.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Format {
+    Ips,
+    Ups,
+    Bps,
+    Vcdiff,
+    Bsdiff,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<Format, Error> {
+        Ok(match format {
+            "ips" => Format::Ips,
+            "ups" => Format::Ups,
+            "bps" => Format::Bps,
+            "vcdiff" => Format::Vcdiff,
+            "bsdiff" => Format::Bsdiff,
+            x => bail!("Unknown patch format: {}", x),
+        })
+    }
+}
+
+impl Format {
+    pub(crate) fn variants() -> &'static [&'static str] {
+        &["ips", "ups", "bps", "vcdiff", "bsdiff"]
+    }
+
+    /// Detects the format of `patch` by its magic.
+    pub(crate) fn detect(patch: &[u8]) -> Result<Format, Error> {
+        if patch.starts_with(ips::MAGIC) {
+            Ok(Format::Ips)
+        } else if patch.starts_with(ups::MAGIC) {
+            Ok(Format::Ups)
+        } else if patch.starts_with(bps::MAGIC) {
+            Ok(Format::Bps)
+        } else if patch.starts_with(vcdiff::MAGIC) {
+            Ok(Format::Vcdiff)
+        } else if patch.starts_with(bsdiff::MAGIC) {
+            Ok(Format::Bsdiff)
+        } else {
+            bail!("Unable to detect patch format, please specify it")
+        }
+    }
+
+    /// Applies `patch` on `source` and returns the patched data.
+    pub(crate) fn apply(self, source: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Format::Ips => ips::apply(source, patch),
+            Format::Ups => ups::apply(source, patch),
+            Format::Bps => bps::apply(source, patch),
+            Format::Vcdiff => vcdiff::apply(source, patch),
+            Format::Bsdiff => bsdiff::apply(source, patch),
+        }
+    }
+
+    /// Creates a patch which transforms `source` into `target`.
+    pub(crate) fn create(self, source: &[u8], target: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Format::Ips => ips::create(source, target),
+            Format::Ups => Ok(ups::create(source, target)),
+            Format::Bps => Ok(bps::create(source, target)),
+            Format::Vcdiff => Ok(vcdiff::create(source, target)),
+            Format::Bsdiff => bsdiff::create(source, target),
+        }
+    }
+}
+
+/// Sequential reader over the bytes of a patch.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn bytes(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() - self.pos < n {
+            bail!("Unexpected end of patch at offset {}", self.pos)
+        }
+        self.pos += n;
+        Ok(&self.data[self.pos - n..self.pos])
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// Reads a variable-length number as used by UPS and BPS.
+    pub(crate) fn number(&mut self) -> Result<usize, Error> {
+        let mut data: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let x = self.byte()?;
+            data = ((x & 0x7f) as usize)
+                .checked_mul(shift)
+                .and_then(|n| n.checked_add(data))
+                .ok_or_else(|| failure::err_msg("Number in patch is too large"))?;
+            if x & 0x80 != 0 {
+                break;
+            }
+            shift = shift
+                .checked_mul(0x80)
+                .ok_or_else(|| failure::err_msg("Number in patch is too large"))?;
+            data = data
+                .checked_add(shift)
+                .ok_or_else(|| failure::err_msg("Number in patch is too large"))?;
+        }
+        Ok(data)
+    }
+}
+
+/// Writes a variable-length number as used by UPS and BPS.
+pub(crate) fn write_number(out: &mut Vec<u8>, mut n: usize) {
+    loop {
+        let x = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(0x80 | x);
+            break;
+        }
+        out.push(x);
+        n -= 1;
+    }
+}
+
+/// Reads the little endian CRC-32 at `offset`.
+pub(crate) fn read_crc32(data: &[u8], offset: usize) -> u32 {
+    let mut b = [0; 4];
+    b.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_number() {
+        for n in vec![0, 1, 127, 128, 129, 16383, 16384, 16511, 16512, 1 << 40] {
+            let mut out = vec![];
+            write_number(&mut out, n);
+            let mut r = Reader::new(&out);
+            assert_eq!(r.number().unwrap(), n);
+            assert_eq!(r.pos(), out.len());
+        }
+        assert!(Reader::new(&[0x00, 0x01]).number().is_err());
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Format::detect(b"PATCHEOF").unwrap(), Format::Ips);
+        assert_eq!(Format::detect(b"UPS1").unwrap(), Format::Ups);
+        assert_eq!(Format::detect(b"BPS1").unwrap(), Format::Bps);
+        assert_eq!(Format::detect(b"\xd6\xc3\xc4\x00").unwrap(), Format::Vcdiff);
+        assert_eq!(Format::detect(b"BSDIFF40").unwrap(), Format::Bsdiff);
+        assert!(Format::detect(b"FOO").is_err());
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut rng = thread_rng();
+        for format in Format::variants() {
+            let format = format.parse::<Format>().unwrap();
+            for _ in 0..30 {
+                let len: usize = rng.gen_range(0, 3000);
+                let source: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+                let mut target = source.clone();
+                for _ in 0..rng.gen_range(0, 10) {
+                    let at = rng.gen_range(0, target.len() + 1);
+                    let len = rng.gen_range(0, 300);
+                    let value: Vec<u8> = if rng.gen() {
+                        vec![rng.gen(); len]
+                    } else {
+                        (0..len).map(|_| rng.gen()).collect()
+                    };
+                    let end = std::cmp::min(at + rng.gen_range(0, 300), target.len());
+                    target.splice(at..end, value);
+                }
+                let patch = format.create(&source, &target).unwrap();
+                assert_eq!(Format::detect(&patch).unwrap(), format);
+                assert_eq!(
+                    format.apply(&source, &patch).unwrap(),
+                    target,
+                    "{:?}",
+                    format
+                );
+            }
+        }
+    }
+}
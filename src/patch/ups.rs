@@ -0,0 +1,142 @@
+use crate::checksum::crc32;
+use crate::patch::{read_crc32, write_number, Reader};
+use failure::{bail, Error};
+
+pub(crate) static MAGIC: &[u8] = b"UPS1";
+/// Source, target and patch CRC-32.
+static FOOTER_LEN: usize = 12;
+
+pub(crate) fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    if patch.len() < MAGIC.len() + FOOTER_LEN || !patch.starts_with(MAGIC) {
+        bail!("Not an UPS patch")
+    }
+    let footer = patch.len() - FOOTER_LEN;
+    if crc32(&patch[0..patch.len() - 4]) != read_crc32(patch, footer + 8) {
+        bail!("UPS patch is corrupted, checksum mismatch")
+    }
+
+    let mut r = Reader::new(&patch[0..footer]);
+    r.bytes(MAGIC.len())?;
+    let source_size = r.number()?;
+    let target_size = r.number()?;
+    if source.len() != source_size || crc32(source) != read_crc32(patch, footer) {
+        bail!("Input does not match the source of the UPS patch")
+    }
+
+    // the target size is taken from the patch, so its memory is reserved
+    // without aborting if it can't be satisfied
+    let mut target = source.to_vec();
+    if target_size > target.len() && target.try_reserve(target_size - target.len()).is_err() {
+        bail!("The UPS target size of {} bytes is too large", target_size)
+    }
+    target.resize(target_size, 0);
+    let len = std::cmp::max(source_size, target_size);
+    let mut pos: usize = 0;
+    while r.pos() < footer {
+        pos = match pos.checked_add(r.number()?) {
+            Some(pos) if pos <= len => pos,
+            _ => bail!("UPS patch writes beyond the end of the data"),
+        };
+        loop {
+            let x = r.byte()?;
+            if x == 0 {
+                pos += 1;
+                break;
+            }
+            if pos == len {
+                bail!("UPS patch writes beyond the end of the data")
+            }
+            if pos < target_size {
+                target[pos] ^= x;
+            }
+            pos += 1;
+        }
+    }
+
+    if crc32(&target) != read_crc32(patch, footer + 4) {
+        bail!("Patched data does not match the target of the UPS patch")
+    }
+    Ok(target)
+}
+
+pub(crate) fn create(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let xor = |i: usize| source.get(i).unwrap_or(&0) ^ target.get(i).unwrap_or(&0);
+    let len = std::cmp::max(source.len(), target.len());
+
+    let mut patch = MAGIC.to_vec();
+    write_number(&mut patch, source.len());
+    write_number(&mut patch, target.len());
+
+    let mut pos = 0;
+    let mut i = 0;
+    while i < len {
+        if xor(i) == 0 {
+            i += 1;
+            continue;
+        }
+        write_number(&mut patch, i - pos);
+        while i < len && xor(i) != 0 {
+            patch.push(xor(i));
+            i += 1;
+        }
+        patch.push(0);
+        i += 1;
+        pos = i;
+    }
+
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    let crc = crc32(&patch);
+    patch.extend_from_slice(&crc.to_le_bytes());
+    patch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create() {
+        let patch = create(b"foobar", b"fooBAR!");
+        assert_eq!(
+            &patch[0..patch.len() - FOOTER_LEN],
+            b"UPS1\x86\x87\x83\x20\x20\x20\x21\x00"
+        );
+        assert_eq!(apply(b"foobar", &patch).unwrap(), b"fooBAR!".to_vec());
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut patch = create(b"foobar", b"fooBAR");
+        assert!(apply(b"foobaz", &patch).is_err());
+        assert!(apply(b"fooba", &patch).is_err());
+        let len = patch.len();
+        patch[len - FOOTER_LEN - 2] ^= 1;
+        assert!(apply(b"foobar", &patch).is_err());
+    }
+
+    #[test]
+    fn test_invalid_sizes() {
+        let with_footer = |body: &[u8]| {
+            let mut patch = body.to_vec();
+            patch.extend_from_slice(&crc32(b"foo").to_le_bytes());
+            patch.extend_from_slice(&[0; 4]);
+            let crc = crc32(&patch);
+            patch.extend_from_slice(&crc.to_le_bytes());
+            patch
+        };
+        let mut body = MAGIC.to_vec();
+        write_number(&mut body, 3);
+        write_number(&mut body, std::usize::MAX);
+        assert!(apply(b"foo", &with_footer(&body)).is_err());
+
+        for offset in vec![3, 4, std::usize::MAX] {
+            let mut body = MAGIC.to_vec();
+            write_number(&mut body, 3);
+            write_number(&mut body, 3);
+            write_number(&mut body, offset);
+            body.extend_from_slice(b"\x01\x00");
+            assert!(apply(b"foo", &with_footer(&body)).is_err());
+        }
+    }
+}
@@ -0,0 +1,546 @@
+use crate::checksum::adler32;
+use crate::patch::Reader;
+use failure::{bail, format_err, Error};
+use lazy_static::lazy_static;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+pub(crate) static MAGIC: &[u8] = &[0xd6, 0xc3, 0xc4, 0x00];
+
+// header indicator
+static VCD_DECOMPRESS: u8 = 0x01;
+static VCD_CODETABLE: u8 = 0x02;
+static VCD_APPHEADER: u8 = 0x04;
+// window indicator
+static VCD_SOURCE: u8 = 0x01;
+static VCD_TARGET: u8 = 0x02;
+static VCD_ADLER32: u8 = 0x04;
+
+// instruction types of the code table
+static NOOP: u8 = 0;
+static ADD: u8 = 1;
+static RUN: u8 = 2;
+static COPY: u8 = 3;
+
+// sizes of the address caches, default of RFC 3284
+static NEAR: usize = 4;
+static SAME: usize = 3;
+
+/// Target data is split into windows of this size when encoding.
+static WINDOW_SIZE: usize = 1 << 20;
+/// Minimal length of a source match which is encoded as COPY.
+static MIN_MATCH: usize = 8;
+/// Minimal length of repeated bytes which are encoded as RUN.
+static MIN_RUN: usize = 8;
+/// Maximal number of source positions which are indexed for matching.
+static MAX_INDEX: usize = 1 << 22;
+
+#[derive(Clone, Copy)]
+struct Instruction {
+    kind: u8,
+    size: usize,
+    mode: u8,
+}
+
+impl Instruction {
+    fn new(kind: u8, size: usize, mode: u8) -> Self {
+        Instruction { kind, size, mode }
+    }
+}
+
+lazy_static! {
+    /// The default instruction code table (RFC 3284 section 5.6).
+    static ref CODE_TABLE: Vec<[Instruction; 2]> = {
+        let noop = Instruction::new(NOOP, 0, 0);
+        let mut table = vec![[Instruction::new(RUN, 0, 0), noop]];
+        for size in 0..=17 {
+            table.push([Instruction::new(ADD, size, 0), noop]);
+        }
+        for mode in 0..=8 {
+            table.push([Instruction::new(COPY, 0, mode), noop]);
+            for size in 4..=18 {
+                table.push([Instruction::new(COPY, size, mode), noop]);
+            }
+        }
+        for mode in 0..=8 {
+            let copy_sizes = if mode < 6 { 4..=6 } else { 4..=4 };
+            for add_size in 1..=4 {
+                for copy_size in copy_sizes.clone() {
+                    table.push([
+                        Instruction::new(ADD, add_size, 0),
+                        Instruction::new(COPY, copy_size, mode),
+                    ]);
+                }
+            }
+        }
+        for mode in 0..=8 {
+            table.push([Instruction::new(COPY, 4, mode), Instruction::new(ADD, 1, 0)]);
+        }
+        table
+    };
+}
+
+fn read_int(r: &mut Reader) -> Result<usize, Error> {
+    let mut n: usize = 0;
+    loop {
+        let b = r.byte()?;
+        n = n
+            .checked_mul(0x80)
+            .ok_or_else(|| format_err!("Integer in VCDIFF data is too large"))?
+            | (b & 0x7f) as usize;
+        if b & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+}
+
+fn write_int(out: &mut Vec<u8>, n: usize) {
+    let mut bytes = vec![(n & 0x7f) as u8];
+    let mut n = n >> 7;
+    while n > 0 {
+        bytes.push(0x80 | (n & 0x7f) as u8);
+        n >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+struct AddressCache {
+    near: Vec<usize>,
+    next: usize,
+    same: Vec<usize>,
+}
+
+impl AddressCache {
+    fn new() -> Self {
+        AddressCache {
+            near: vec![0; NEAR],
+            next: 0,
+            same: vec![0; SAME * 256],
+        }
+    }
+
+    fn decode(&mut self, here: usize, mode: u8, addr: &mut Reader) -> Result<usize, Error> {
+        let mode = mode as usize;
+        let a = if mode == 0 {
+            Some(read_int(addr)?)
+        } else if mode == 1 {
+            here.checked_sub(read_int(addr)?)
+        } else if mode < 2 + NEAR {
+            self.near[mode - 2].checked_add(read_int(addr)?)
+        } else {
+            Some(self.same[(mode - 2 - NEAR) * 256 + addr.byte()? as usize])
+        };
+        let a = a.ok_or_else(|| format_err!("Invalid COPY address in VCDIFF data"))?;
+
+        self.near[self.next] = a;
+        self.next = (self.next + 1) % NEAR;
+        self.same[a % (SAME * 256)] = a;
+        Ok(a)
+    }
+}
+
+fn decode_window(
+    segment: &[u8],
+    window_len: usize,
+    data: &[u8],
+    inst: &[u8],
+    addr: &[u8],
+) -> Result<Vec<u8>, Error> {
+    // the window grows as it is decoded instead of trusting its length
+    let mut out: Vec<u8> = vec![];
+    let mut data = Reader::new(data);
+    let mut addr = Reader::new(addr);
+    let inst_len = inst.len();
+    let mut inst = Reader::new(inst);
+    let mut cache = AddressCache::new();
+
+    while inst.pos() < inst_len {
+        let code = inst.byte()?;
+        for i in CODE_TABLE[code as usize].iter() {
+            if i.kind == NOOP {
+                continue;
+            }
+            let size = if i.size == 0 {
+                read_int(&mut inst)?
+            } else {
+                i.size
+            };
+            match out.len().checked_add(size) {
+                Some(end) if end <= window_len => (),
+                _ => bail!("VCDIFF instruction writes beyond the target window"),
+            }
+
+            if i.kind == ADD {
+                out.extend_from_slice(data.bytes(size)?);
+            } else if i.kind == RUN {
+                let b = data.byte()?;
+                if out.try_reserve(size).is_err() {
+                    bail!("VCDIFF RUN of {} bytes does not fit into memory", size)
+                }
+                out.resize(out.len() + size, b);
+            } else {
+                let here = segment.len() + out.len();
+                let a = cache.decode(here, i.mode, &mut addr)?;
+                let end = match a.checked_add(size) {
+                    Some(end) if a < here => end,
+                    _ => bail!("VCDIFF COPY address {} is out of range", a),
+                };
+                if end <= segment.len() {
+                    out.extend_from_slice(&segment[a..end]);
+                } else {
+                    if out.try_reserve(size).is_err() {
+                        bail!("VCDIFF COPY of {} bytes does not fit into memory", size)
+                    }
+                    // may overlap with the bytes being written
+                    for p in a..end {
+                        let b = if p < segment.len() {
+                            segment[p]
+                        } else {
+                            out[p - segment.len()]
+                        };
+                        out.push(b);
+                    }
+                }
+            }
+        }
+    }
+
+    if out.len() != window_len {
+        bail!("VCDIFF window is shorter than announced")
+    }
+    Ok(out)
+}
+
+pub(crate) fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut r = Reader::new(patch);
+    if !patch.starts_with(MAGIC) {
+        bail!("Not a VCDIFF patch")
+    }
+    r.bytes(MAGIC.len())?;
+    let header = r.byte()?;
+    if header & VCD_DECOMPRESS != 0 {
+        bail!("VCDIFF secondary compression is not supported")
+    }
+    if header & VCD_CODETABLE != 0 {
+        bail!("VCDIFF application-defined code tables are not supported")
+    }
+    if header & VCD_APPHEADER != 0 {
+        let len = read_int(&mut r)?;
+        r.bytes(len)?;
+    }
+
+    let mut target: Vec<u8> = vec![];
+    while r.pos() < patch.len() {
+        let indicator = r.byte()?;
+        let segment: Cow<[u8]> = if indicator & (VCD_SOURCE | VCD_TARGET) != 0 {
+            let size = read_int(&mut r)?;
+            let pos = read_int(&mut r)?;
+            let (name, data) = if indicator & VCD_SOURCE != 0 {
+                (
+                    "source",
+                    source.get(pos..pos.saturating_add(size)).map(Cow::from),
+                )
+            } else {
+                (
+                    "target",
+                    target
+                        .get(pos..pos.saturating_add(size))
+                        .map(|s| Cow::from(s.to_vec())),
+                )
+            };
+            data.ok_or_else(|| format_err!("VCDIFF window references data beyond the {}", name))?
+        } else {
+            Cow::from(&[][..])
+        };
+
+        read_int(&mut r)?; // length of the delta encoding
+        let window_len = read_int(&mut r)?;
+        if r.byte()? != 0 {
+            bail!("VCDIFF compressed sections are not supported")
+        }
+        let data_len = read_int(&mut r)?;
+        let inst_len = read_int(&mut r)?;
+        let addr_len = read_int(&mut r)?;
+        let checksum = if indicator & VCD_ADLER32 != 0 {
+            Some(r.bytes(4)?.iter().fold(0, |n, b| (n << 8) | *b as u32))
+        } else {
+            None
+        };
+        let data = r.bytes(data_len)?;
+        let inst = r.bytes(inst_len)?;
+        let addr = r.bytes(addr_len)?;
+
+        let window = decode_window(&segment, window_len, data, inst, addr)?;
+        if let Some(checksum) = checksum {
+            if adler32(&window) != checksum {
+                bail!("VCDIFF window checksum mismatch")
+            }
+        }
+        target.extend_from_slice(&window);
+    }
+    Ok(target)
+}
+
+/// Index of source positions by the bytes which follow them.
+struct SourceIndex<'a> {
+    source: &'a [u8],
+    positions: HashMap<&'a [u8], usize>,
+}
+
+impl<'a> SourceIndex<'a> {
+    fn new(source: &'a [u8]) -> Self {
+        let step = std::cmp::max(1, source.len() / MAX_INDEX);
+        let mut positions = HashMap::new();
+        if source.len() >= MIN_MATCH {
+            for pos in (0..=source.len() - MIN_MATCH).step_by(step) {
+                positions
+                    .entry(&source[pos..pos + MIN_MATCH])
+                    .or_insert(pos);
+            }
+        }
+        SourceIndex { source, positions }
+    }
+
+    /// Returns the source position and length of a match for `data`.
+    fn find(&self, data: &[u8]) -> Option<(usize, usize)> {
+        let pos = *self.positions.get(&data[0..MIN_MATCH])?;
+        let len = self.source[pos..]
+            .iter()
+            .zip(data)
+            .take_while(|(a, b)| a == b)
+            .count();
+        Some((pos, len))
+    }
+}
+
+#[derive(Default)]
+struct Sections {
+    data: Vec<u8>,
+    inst: Vec<u8>,
+    addr: Vec<u8>,
+}
+
+impl Sections {
+    fn add(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        if bytes.len() <= 17 {
+            self.inst.push(1 + bytes.len() as u8);
+        } else {
+            self.inst.push(1);
+            write_int(&mut self.inst, bytes.len());
+        }
+        self.data.extend_from_slice(bytes);
+    }
+
+    fn run(&mut self, b: u8, size: usize) {
+        self.inst.push(0);
+        write_int(&mut self.inst, size);
+        self.data.push(b);
+    }
+
+    /// COPY with VCD_SELF addressing.
+    fn copy(&mut self, addr: usize, size: usize) {
+        if (4..=18).contains(&size) {
+            self.inst.push(16 + size as u8);
+        } else {
+            self.inst.push(19);
+            write_int(&mut self.inst, size);
+        }
+        write_int(&mut self.addr, addr);
+    }
+
+    /// Stores bytes which have no match, repetitions are encoded as RUN.
+    fn literal(&mut self, bytes: &[u8]) {
+        let mut begin = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let n = bytes[i..].iter().take_while(|b| **b == bytes[i]).count();
+            if n >= MIN_RUN {
+                self.add(&bytes[begin..i]);
+                self.run(bytes[i], n);
+                begin = i + n;
+            }
+            i += n;
+        }
+        self.add(&bytes[begin..]);
+    }
+}
+
+fn encode_window(index: &SourceIndex, window: &[u8]) -> Sections {
+    let mut sections = Sections::default();
+    let mut literal = 0;
+    let mut i = 0;
+    while i + MIN_MATCH <= window.len() {
+        if let Some((pos, len)) = index.find(&window[i..]) {
+            if len >= MIN_MATCH {
+                let back = index.source[..pos]
+                    .iter()
+                    .rev()
+                    .zip(window[literal..i].iter().rev())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                sections.literal(&window[literal..i - back]);
+                sections.copy(pos - back, len + back);
+                i += len;
+                literal = i;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    sections.literal(&window[literal..]);
+    sections
+}
+
+/// Creates a VCDIFF delta (default code table, no secondary compression)
+/// which copies from the source where possible.
+pub(crate) fn create(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = MAGIC.to_vec();
+    patch.push(0);
+
+    let index = SourceIndex::new(source);
+    for window in target.chunks(WINDOW_SIZE) {
+        let sections = encode_window(&index, window);
+        if source.is_empty() {
+            patch.push(0);
+        } else {
+            patch.push(VCD_SOURCE);
+            write_int(&mut patch, source.len());
+            write_int(&mut patch, 0);
+        }
+
+        let mut delta = vec![];
+        write_int(&mut delta, window.len());
+        delta.push(0);
+        write_int(&mut delta, sections.data.len());
+        write_int(&mut delta, sections.inst.len());
+        write_int(&mut delta, sections.addr.len());
+        delta.extend_from_slice(&sections.data);
+        delta.extend_from_slice(&sections.inst);
+        delta.extend_from_slice(&sections.addr);
+
+        write_int(&mut patch, delta.len());
+        patch.extend_from_slice(&delta);
+    }
+    patch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_int() {
+        for (n, exp) in [
+            (0, vec![0x00]),
+            (127, vec![0x7f]),
+            (128, vec![0x81, 0x00]),
+            (200, vec![0x81, 0x48]),
+            (123_456_789, vec![0xba, 0xef, 0x9a, 0x15]),
+        ] {
+            let mut out = vec![];
+            write_int(&mut out, n);
+            assert_eq!(out, exp);
+            assert_eq!(read_int(&mut Reader::new(&out)).unwrap(), n);
+        }
+    }
+
+    #[test]
+    fn test_code_table() {
+        assert_eq!(CODE_TABLE.len(), 256);
+        let i = CODE_TABLE[163];
+        assert!(i[0].kind == ADD && i[0].size == 1 && i[1].kind == COPY && i[1].size == 4);
+        let i = CODE_TABLE[255];
+        assert!(i[0].kind == COPY && i[0].mode == 8 && i[1].kind == ADD);
+    }
+
+    fn window(indicator: u8, checksum: Option<u32>) -> Vec<u8> {
+        // COPY self, COPY here, RUN, COPY near, COPY same
+        let data = b"!";
+        let inst = [19, 3, 35, 3, 0, 4, 51, 3, 115, 3];
+        let addr = [3, 9, 0, 0];
+        let mut delta = vec![16, 0, 1, 10, 4];
+        if let Some(checksum) = checksum {
+            delta.extend_from_slice(&checksum.to_be_bytes());
+        }
+        delta.extend_from_slice(data);
+        delta.extend_from_slice(&inst);
+        delta.extend_from_slice(&addr);
+
+        let mut patch = MAGIC.to_vec();
+        patch.extend_from_slice(&[0, indicator, 6, 0, delta.len() as u8]);
+        patch.extend_from_slice(&delta);
+        patch
+    }
+
+    #[test]
+    fn test_apply() {
+        let target = b"barfoo!!!!barfoo".to_vec();
+        assert_eq!(apply(b"foobar", &window(VCD_SOURCE, None)).unwrap(), target);
+
+        let checksum = adler32(&target);
+        let patch = window(VCD_SOURCE | VCD_ADLER32, Some(checksum));
+        assert_eq!(apply(b"foobar", &patch).unwrap(), target);
+        let patch = window(VCD_SOURCE | VCD_ADLER32, Some(checksum + 1));
+        assert!(apply(b"foobar", &patch).is_err());
+
+        assert!(apply(b"foo", &window(VCD_SOURCE, None)).is_err());
+        let mut patch = window(VCD_SOURCE, None);
+        patch.pop();
+        assert!(apply(b"foobar", &patch).is_err());
+    }
+
+    #[test]
+    fn test_invalid_sizes() {
+        // a window of `window_len` bytes with a single instruction of `size`
+        let patch = |window_len: usize, inst: u8, size: usize, addr: usize| {
+            let mut delta = vec![];
+            write_int(&mut delta, window_len);
+            delta.push(0);
+            let mut inst_section = vec![inst];
+            write_int(&mut inst_section, size);
+            let mut addr_section = vec![];
+            write_int(&mut addr_section, addr);
+            write_int(&mut delta, 1);
+            write_int(&mut delta, inst_section.len());
+            write_int(&mut delta, addr_section.len());
+            delta.push(b'x');
+            delta.extend_from_slice(&inst_section);
+            delta.extend_from_slice(&addr_section);
+
+            let mut patch = MAGIC.to_vec();
+            patch.extend_from_slice(&[0, VCD_SOURCE, 6, 0]);
+            write_int(&mut patch, delta.len());
+            patch.extend_from_slice(&delta);
+            patch
+        };
+        let huge = std::usize::MAX;
+        // RUN and COPY (mode 0) with explicit size
+        for (window_len, inst, size, addr) in vec![
+            (huge, 0, 0, 0),
+            (huge, 0, huge >> 2, 0),
+            (huge, 19, huge - 1, 3),
+            (huge, 19, 2, huge),
+            (huge, 19, 1 << 60, 3),
+        ] {
+            assert!(apply(b"foobar", &patch(window_len, inst, size, addr)).is_err());
+        }
+        assert_eq!(
+            apply(b"foobar", &patch(2, 19, 2, 3)).unwrap(),
+            b"ba".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_create() {
+        let source: Vec<u8> = (0..=255).collect();
+        let mut target = vec![0xaa; 100];
+        target.extend_from_slice(&source[16..200]);
+        target.extend_from_slice(b"tail");
+        let patch = create(&source, &target);
+        assert!(patch.len() < 32, "patch too large: {}", patch.len());
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+    }
+}
@@ -1,7 +1,10 @@
+use crate::elf::{self, Elf};
 use failure::{bail, Error};
 use humanize_rs::bytes::Bytes;
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::cell::RefCell;
+use std::ops::Range;
 use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -10,23 +13,76 @@ pub(crate) struct Position {
     pub(crate) end: Option<usize>,
 }
 
+impl Position {
+    /// Creates a position covering `count` bytes from `begin`, `count` has
+    /// to be >= 1.
+    pub(crate) fn with_count(begin: usize, count: usize) -> Position {
+        debug_assert!(count > 0);
+        Position {
+            begin,
+            end: Some(begin + count - 1),
+        }
+    }
+
+    /// Returns the covered range of an input of `len` bytes, a position
+    /// without end reaches to the end of the input.
+    pub(crate) fn range(&self, len: usize) -> Result<Range<usize>, Error> {
+        let end = match self.end {
+            Some(end) => end + 1,
+            None => len,
+        };
+        if self.begin > len || end > len {
+            bail!("<POSITION> {} exceeds the input of {} bytes", self, len)
+        }
+        Ok(self.begin..end)
+    }
+}
+
+/// Describes the bytes `range` of a file as position. An empty range only
+/// marks the point of an insertion or deletion and is described as
+/// `at <offset>`, because a sole begin would reach to the end of the file.
+pub(crate) fn describe(range: &Range<usize>) -> String {
+    if range.is_empty() {
+        format!("at {}", range.start)
+    } else {
+        Position::with_count(range.start, range.len()).to_string()
+    }
+}
+
+/// Parses a decimal number with an optional unit (e.g. `4kib`) or a
+/// hexadecimal number with `0x` prefix.
+pub(crate) fn parse_number(number: &str) -> Result<usize, Error> {
+    if number.starts_with("0x") || number.starts_with("0X") {
+        Ok(usize::from_str_radix(&number[2..], 16)?)
+    } else {
+        Ok(number.parse::<Bytes>()?.size())
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.end {
+            Some(end) => write!(f, "{}+{}", self.begin, end - self.begin + 1),
+            None => write!(f, "{}", self.begin),
+        }
+    }
+}
+
 impl FromStr for Position {
     type Err = Error;
 
     fn from_str(position: &str) -> Result<Position, Error> {
         lazy_static! {
             static ref RE: Regex = Regex::new(
-                r"^(?P<begin>\d+[kmgtpeibKMGTPEIB]{0,3})(?P<end>(?P<behavior>\+|:|(:=))(?P<to>\d+[kmgtpeibKMGTPEIB]{0,3}))?$"
+                r"^(?P<begin>0[xX][[:xdigit:]]+|\d+[kmgtpeibKMGTPEIB]{0,3})(?P<end>(?P<behavior>\+|:|(:=))(?P<to>0[xX][[:xdigit:]]+|\d+[kmgtpeibKMGTPEIB]{0,3}))?$"
             )
             .unwrap();
         }
 
         if let Some(caps) = RE.captures(position) {
-            let begin = caps.name("begin").unwrap().as_str().parse::<Bytes>()?;
-            let begin = begin.size();
+            let begin = parse_number(caps.name("begin").unwrap().as_str())?;
             if let Some(_) = caps.name("end") {
-                let to = caps.name("to").unwrap().as_str().parse::<Bytes>()?;
-                let to = to.size();
+                let to = parse_number(caps.name("to").unwrap().as_str())?;
 
                 let end = match caps.name("behavior").unwrap().as_str() {
                     "+" => {
@@ -62,9 +118,59 @@ impl FromStr for Position {
     }
 }
 
+/// Parses the positions of a file, references to the sections, symbols or
+/// virtual addresses of an ELF file are resolved with the file, which is only
+/// read if a reference occurs.
+pub(crate) struct PositionParser<'a> {
+    read: Box<dyn Fn() -> Result<Vec<u8>, Error> + 'a>,
+    elf: RefCell<Option<Elf>>,
+}
+
+impl<'a> PositionParser<'a> {
+    /// Creates a parser for the file which `read` returns the content of.
+    pub(crate) fn new(read: impl Fn() -> Result<Vec<u8>, Error> + 'a) -> Self {
+        PositionParser {
+            read: Box::new(read),
+            elf: RefCell::new(None),
+        }
+    }
+
+    fn with_elf<T>(&self, f: impl FnOnce(&Elf) -> Result<T, Error>) -> Result<T, Error> {
+        let mut elf = self.elf.borrow_mut();
+        if elf.is_none() {
+            *elf = Some(Elf::parse(&(self.read)()?)?);
+        }
+        f(elf.as_ref().unwrap())
+    }
+
+    /// Resolves the ELF references of `position`, the result is a plain
+    /// position.
+    pub(crate) fn resolve(&self, position: &str) -> Result<String, Error> {
+        if elf::is_reference(position) {
+            self.with_elf(|elf| elf.resolve_position(position))
+        } else {
+            Ok(position.to_string())
+        }
+    }
+
+    /// Resolves an ELF reference used as offset, a section or symbol stands
+    /// for its first byte. The result is a decimal number.
+    pub(crate) fn offset(&self, offset: &str) -> Result<String, Error> {
+        if elf::is_reference(offset) {
+            self.with_elf(|elf| Ok(elf.resolve_offset(offset)?.to_string()))
+        } else {
+            Ok(offset.to_string())
+        }
+    }
+
+    pub(crate) fn position(&self, position: &str) -> Result<Position, Error> {
+        self.resolve(position)?.parse::<Position>()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Position;
+    use super::{describe, parse_number, Position, PositionParser};
     use humanize_rs::bytes::{Bytes, Unit};
 
     #[test]
@@ -76,6 +182,9 @@ mod tests {
         for t in test_vec {
             assert!(t.parse::<Position>().is_err());
         }
+        for t in vec!["0x", "0xg", "0x10:0x10", "0x1kib", "0x+1"] {
+            assert!(t.parse::<Position>().is_err());
+        }
     }
 
     #[test]
@@ -136,6 +245,8 @@ mod tests {
             ("0:=10", 0, Some(10)),
             ("512:1024", 512, Some(1023)),
             ("512:=1024", 512, Some(1024)),
+            ("0x100:0x200", 256, Some(511)),
+            ("0XfF+0x10", 255, Some(270)),
             (
                 "3M:=5mib",
                 (Bytes::new(3, Unit::MByte).unwrap().size()),
@@ -156,4 +267,66 @@ mod tests {
             assert_eq!(r.unwrap(), Position { begin, end });
         }
     }
+
+    #[test]
+    fn test_display() {
+        for format in vec!["0", "123", "0+1", "512+512"] {
+            let p = format.parse::<Position>().unwrap();
+            assert_eq!(format, p.to_string());
+        }
+        assert_eq!("10:=20".parse::<Position>().unwrap().to_string(), "10+11");
+        assert_eq!(Position::with_count(16, 4).to_string(), "16+4");
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe(&(16..16)), "at 16");
+        assert_eq!(describe(&(0..0)), "at 0");
+        assert_eq!(describe(&(16..20)), "16+4");
+
+        // a description reads back as the same range of the file
+        for range in vec![2..2, 0..0, 5..5, 2..5, 0..1, 4..5] {
+            let text = describe(&range);
+            let parsed = match text.strip_prefix("at ") {
+                Some(offset) => {
+                    let offset = parse_number(offset).unwrap();
+                    offset..offset
+                }
+                None => text.parse::<Position>().unwrap().range(5).unwrap(),
+            };
+            assert_eq!(parsed, range);
+        }
+    }
+
+    #[test]
+    fn test_covered_range() {
+        assert_eq!("2+3".parse::<Position>().unwrap().range(5).unwrap(), 2..5);
+        assert_eq!("2".parse::<Position>().unwrap().range(5).unwrap(), 2..5);
+        assert_eq!("5".parse::<Position>().unwrap().range(5).unwrap(), 5..5);
+        assert!("2+4".parse::<Position>().unwrap().range(5).is_err());
+        assert!("6".parse::<Position>().unwrap().range(5).is_err());
+    }
+
+    #[test]
+    fn test_parser() {
+        let parser = PositionParser::new(|| Ok(crate::elf::tests::elf()));
+        assert_eq!(parser.resolve("elf:sym:main").unwrap(), "96+16");
+        assert_eq!(parser.offset("elf:.rodata").unwrap(), "112");
+        assert_eq!(
+            parser.position("elf:.text:elf:.rodata").unwrap(),
+            Position {
+                begin: 96,
+                end: Some(111)
+            }
+        );
+
+        // the file is only read for references
+        let parser = PositionParser::new(|| failure::bail!("not read"));
+        assert_eq!(parser.resolve("0x10+4").unwrap(), "0x10+4");
+        assert_eq!(parser.offset("0x10").unwrap(), "0x10");
+        assert!(parser.resolve("elf:.text").is_err());
+        assert!(PositionParser::new(|| Ok(b"no elf".to_vec()))
+            .resolve("elf:.text")
+            .is_err());
+    }
 }